@@ -0,0 +1,298 @@
+use std::sync::Arc;
+
+use plonk::{
+    ark_bn254::{Bn254, Fr},
+    ark_serialize::{CanonicalDeserialize, CanonicalSerialize},
+    kzg10::{Commitment, PCKey, VKey},
+    prover::{Prover, ProverKey},
+    EvaluationDomain, GeneralEvaluationDomain,
+};
+use prover::{
+    parameters::{load_prover_key, load_verifier_comms},
+    utils::convert_vk_data_array,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{Error, Result},
+    header::{
+        circuit_id, hash_field, load_artifact, store_artifact, ArtifactHeader, ArtifactKind,
+        TrustedChecksums, FORMAT_VERSION,
+    },
+    openid_zk_args::encode_vk_data,
+};
+
+pub type OpenIdProver = Prover<Fr, GeneralEvaluationDomain<Fr>, Bn254>;
+
+/// Setup parameters, proving key and verifier comms, ready to prove and verify with.
+///
+/// Clones share the setup parameters but get their own prover, and with it a copy of the
+/// proving key, so that they can prove concurrently.
+#[derive(Clone)]
+pub struct ProvingArtifacts {
+    pub pckey: Arc<PCKey<Bn254>>,
+    pub prover: OpenIdProver,
+    pub vc: Vec<Commitment<Bn254>>,
+    pub sha256_of_srs: Vec<u8>,
+    pub circuit_id: [u8; 32],
+}
+
+impl ProvingArtifacts {
+    pub fn new(
+        pckey: PCKey<Bn254>,
+        prover: OpenIdProver,
+        vc: Vec<Commitment<Bn254>>,
+        circuit_id: [u8; 32],
+    ) -> Self {
+        let sha256_of_srs = pckey.sha256_of_srs();
+        Self::with_srs_hash(pckey, prover, vc, circuit_id, sha256_of_srs)
+    }
+
+    fn with_srs_hash(
+        pckey: PCKey<Bn254>,
+        mut prover: OpenIdProver,
+        vc: Vec<Commitment<Bn254>>,
+        circuit_id: [u8; 32],
+        sha256_of_srs: Vec<u8>,
+    ) -> Self {
+        prover.insert_verifier_comms(&vc);
+        Self {
+            pckey: Arc::new(pckey),
+            prover,
+            vc,
+            sha256_of_srs,
+            circuit_id,
+        }
+    }
+
+    /// Load the artifacts, refusing a proving key or verifier comms whose header names another
+    /// SRS or circuit.
+    pub fn load(params_path: &str, pk_path: &str, vc_path: &str) -> Result<Self> {
+        Self::load_trusted(params_path, pk_path, vc_path, &TrustedChecksums::default())
+    }
+
+    /// [`Self::load`], skipping the point checks of the files with a trusted checksum.
+    pub fn load_trusted(
+        params_path: &str,
+        pk_path: &str,
+        vc_path: &str,
+        checksums: &TrustedChecksums,
+    ) -> Result<Self> {
+        let (pckey, params_header) = load_artifact(
+            params_path,
+            ArtifactKind::Params,
+            checksums.get(params_path),
+            prover::parameters::load_params,
+        )?;
+        let (pk, pk_header) = load_artifact(
+            pk_path,
+            ArtifactKind::ProvingKey,
+            checksums.get(pk_path),
+            load_prover_key,
+        )?;
+        let (vc, vc_header) = load_artifact(
+            vc_path,
+            ArtifactKind::VerifierComms,
+            checksums.get(vc_path),
+            load_verifier_comms,
+        )?;
+        let circuit_id = match &pk_header {
+            Some(header) => header.circuit_id,
+            None => circuit_id(&pk)?,
+        };
+        // Hashing the SRS takes as long as reading it, a trusted header already records it.
+        let sha256_of_srs = match params_header {
+            Some(header) if checksums.get(params_path).is_some() => header.srs_hash.to_vec(),
+            _ => pckey.sha256_of_srs(),
+        };
+
+        let artifacts = Self::with_srs_hash(pckey, Prover::new(pk), vc, circuit_id, sha256_of_srs);
+        let verifying_artifacts = artifacts.verifying_artifacts();
+        for (path, header) in [(pk_path, pk_header), (vc_path, vc_header)] {
+            if let Some(header) = header {
+                verifying_artifacts.check_header(&header, path)?;
+            }
+        }
+        Ok(artifacts)
+    }
+
+    pub fn verifying_artifacts(&self) -> VerifyingArtifacts {
+        VerifyingArtifacts {
+            domain: self.prover.domain,
+            vc: self.vc.clone(),
+            vk: self.pckey.vk.clone(),
+            sha256_of_srs: self.sha256_of_srs.clone(),
+            circuit_id: Some(self.circuit_id),
+            proof_commitments: None,
+        }
+    }
+
+    /// The header of a `kind` artifact made with these artifacts, such as a proof.
+    pub fn header(&self, kind: ArtifactKind) -> Result<ArtifactHeader> {
+        self.verifying_artifacts().header(kind)
+    }
+}
+
+/// Load setup parameters written with or without a header.
+pub fn load_params(path: &str) -> Result<PCKey<Bn254>> {
+    let (pckey, _) = load_artifact(
+        path,
+        ArtifactKind::Params,
+        None,
+        prover::parameters::load_params,
+    )?;
+    Ok(pckey)
+}
+
+pub fn store_params(pckey: &PCKey<Bn254>, path: &str) -> Result<()> {
+    let header = ArtifactHeader {
+        version: FORMAT_VERSION,
+        kind: ArtifactKind::Params,
+        circuit_id: [0; 32],
+        srs_hash: hash_field(&pckey.sha256_of_srs()),
+        vk_hash: [0; 32],
+    };
+    store_artifact(path, &header, pckey)
+}
+
+/// Store `pk` with the header of the keys in `verifying_artifacts`.
+pub fn store_prover_key(
+    pk: &ProverKey<Fr>,
+    verifying_artifacts: &VerifyingArtifacts,
+    path: &str,
+) -> Result<()> {
+    let header = verifying_artifacts.header(ArtifactKind::ProvingKey)?;
+    store_artifact(path, &header, pk)
+}
+
+pub fn store_verifier_comms(verifying_artifacts: &VerifyingArtifacts, path: &str) -> Result<()> {
+    let header = verifying_artifacts.header(ArtifactKind::VerifierComms)?;
+    store_artifact(path, &header, &verifying_artifacts.vc)
+}
+
+/// Everything needed to verify a proof and nothing needed to produce one.
+///
+/// Stored behind an [`ArtifactHeader`] as the domain size, the verifier comms, the KZG verifying key (whose `beta_h` goes
+/// into the contracts' vk data), the SRS hash and the commitment count of a proof, a few
+/// kilobytes against the proving key's hundreds of megabytes.
+#[derive(Clone)]
+pub struct VerifyingArtifacts {
+    pub domain: GeneralEvaluationDomain<Fr>,
+    pub vc: Vec<Commitment<Bn254>>,
+    pub vk: VKey<Bn254>,
+    pub sha256_of_srs: Vec<u8>,
+    /// Unknown for a verifier stored without a header.
+    pub circuit_id: Option<[u8; 32]>,
+    /// How many commitments a proof of the circuit carries, counted by `gen-keys`. Unknown for
+    /// artifacts loaded from a proving key and for verifiers stored before it was recorded.
+    pub proof_commitments: Option<usize>,
+}
+
+impl VerifyingArtifacts {
+    pub fn new(
+        pckey: &PCKey<Bn254>,
+        prover: &OpenIdProver,
+        vc: Vec<Commitment<Bn254>>,
+        circuit_id: [u8; 32],
+    ) -> Self {
+        Self {
+            domain: prover.domain,
+            vc,
+            vk: pckey.vk.clone(),
+            sha256_of_srs: pckey.sha256_of_srs(),
+            circuit_id: Some(circuit_id),
+            proof_commitments: None,
+        }
+    }
+
+    pub fn header(&self, kind: ArtifactKind) -> Result<ArtifactHeader> {
+        Ok(ArtifactHeader {
+            version: FORMAT_VERSION,
+            kind,
+            circuit_id: self.circuit_id.unwrap_or_default(),
+            srs_hash: hash_field(&self.sha256_of_srs),
+            vk_hash: self.vk_hash()?,
+        })
+    }
+
+    /// Check the header `found` in `path` against these artifacts.
+    pub fn check_header(&self, found: &ArtifactHeader, path: &str) -> Result<()> {
+        let mut expected = self.header(found.kind)?;
+        // Without its circuit id, the vk hash still ties the artifact to the circuit.
+        if self.circuit_id.is_none() {
+            expected.circuit_id = found.circuit_id;
+        }
+        expected.check(found, path)
+    }
+
+    /// `sha256` of the `vkdata` the contracts are configured with.
+    pub fn vk_hash(&self) -> Result<[u8; 32]> {
+        let vk_data = convert_vk_data_array(self.domain, &self.vc, self.vk.beta_h);
+        Ok(Sha256::digest(encode_vk_data(&vk_data)?).into())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = vec![];
+        (self.domain.size() as u64)
+            .serialize(&mut bytes)
+            .and_then(|_| self.vc.serialize(&mut bytes))
+            .and_then(|_| self.vk.serialize(&mut bytes))
+            .and_then(|_| self.sha256_of_srs.serialize(&mut bytes))
+            .and_then(|_| match self.proof_commitments {
+                Some(count) => (count as u64).serialize(&mut bytes),
+                None => Ok(()),
+            })
+            .map_err(|e| Error::InvalidArtifact(format!("verifying key: {:?}", e)))?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self> {
+        let invalid = |e| Error::InvalidArtifact(format!("verifying key: {:?}", e));
+        let domain_size = u64::deserialize(&mut bytes).map_err(invalid)?;
+        let vc = Vec::<Commitment<Bn254>>::deserialize(&mut bytes).map_err(invalid)?;
+        let vk = VKey::<Bn254>::deserialize(&mut bytes).map_err(invalid)?;
+        let sha256_of_srs = Vec::<u8>::deserialize(&mut bytes).map_err(invalid)?;
+        // Verifiers stored before the count was recorded end here.
+        let proof_commitments = match bytes.is_empty() {
+            true => None,
+            false => Some(u64::deserialize(&mut bytes).map_err(invalid)? as usize),
+        };
+        if !bytes.is_empty() {
+            return Err(Error::InvalidArtifact(format!(
+                "verifying key: {} trailing bytes",
+                bytes.len()
+            )));
+        }
+        let domain = GeneralEvaluationDomain::new(domain_size as usize)
+            .filter(|domain| domain.size() as u64 == domain_size)
+            .ok_or_else(|| {
+                Error::InvalidArtifact(format!("verifying key: domain size {}", domain_size))
+            })?;
+        Ok(Self {
+            domain,
+            vc,
+            vk,
+            sha256_of_srs,
+            circuit_id: None,
+            proof_commitments,
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|e| Error::io_at(path, e))?;
+        match ArtifactHeader::split(&bytes, ArtifactKind::Verifier, path)? {
+            Some((header, body)) => {
+                let mut artifacts = Self::from_bytes(body)?;
+                artifacts.circuit_id = Some(header.circuit_id);
+                artifacts.check_header(&header, path)?;
+                Ok(artifacts)
+            }
+            None => Self::from_bytes(&bytes),
+        }
+    }
+
+    pub fn store(&self, path: &str) -> Result<()> {
+        let bytes = self.header(ArtifactKind::Verifier)?.wrap(&self.to_bytes()?);
+        std::fs::write(path, bytes).map_err(|e| Error::io_at(path, e))
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Write},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+use rand::thread_rng;
+use serde::Serialize;
+
+use crate::{
+    error::{Error, Result},
+    openid_zk_args::{openid_zk_args, ZkArgsRequest, ZkArgsResponse},
+    policy::TokenChecks,
+    ProvingArtifacts,
+};
+
+/// How many records of a batch were proved and how many failed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub proved: usize,
+    pub failed: usize,
+}
+
+/// Prove every [`ZkArgsRequest`] line of `input` on `jobs` threads, writing one JSON line per
+/// record to `output` in input order.
+///
+/// Each thread proves with its own clone of `artifacts`, re-cloned after a panic. Results carry the 1-based `line` of
+/// their record, and a record that fails, or whose proof panics, gets an `error` object instead
+/// of the ZK args without stopping the batch. Blank lines are skipped.
+pub fn prove_batch<I: BufRead, W: Write>(
+    artifacts: ProvingArtifacts,
+    checks: &TokenChecks,
+    jobs: usize,
+    input: I,
+    output: W,
+) -> Result<BatchSummary> {
+    run_batch(artifacts, jobs, input, output, |artifacts, record| {
+        prove_record(artifacts, checks, record)
+    })
+}
+
+/// Run `f` on every record of `input` with a clone of `worker` per thread.
+///
+/// A panic may leave the clone half updated, so the thread replaces it with a fresh one.
+fn run_batch<T, R, I, W>(
+    worker: T,
+    jobs: usize,
+    input: I,
+    mut output: W,
+    f: impl Fn(&mut T, &str) -> Result<R> + Sync,
+) -> Result<BatchSummary>
+where
+    T: Clone + Send + Sync,
+    R: Serialize + Send,
+    I: BufRead,
+    W: Write,
+{
+    let mut records = vec![];
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            records.push((i + 1, line));
+        }
+    }
+
+    let jobs = jobs.clamp(1, records.len().max(1));
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    let mut summary = BatchSummary::default();
+
+    thread::scope(|s| -> Result<()> {
+        for _ in 0..jobs {
+            let (template, records, next, tx, f) = (&worker, &records, &next, tx.clone(), &f);
+            s.spawn(move || {
+                let mut worker = template.clone();
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((line, record)) = records.get(i) else {
+                        break;
+                    };
+                    // A panicking record fails alone instead of taking the batch down.
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| f(&mut worker, record)))
+                        .unwrap_or_else(|panic| {
+                            worker = template.clone();
+                            Err(Error::from_panic(panic))
+                        });
+                    if tx.send((i, *line, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        // Results arrive in completion order, hold them back until their turn.
+        let mut pending = BTreeMap::new();
+        let mut written = 0;
+        for (i, line, result) in rx {
+            pending.insert(i, (line, result));
+            while let Some((line, result)) = pending.remove(&written) {
+                let value = match result {
+                    Ok(response) => {
+                        summary.proved += 1;
+                        let mut value = serde_json::to_value(response)?;
+                        value["line"] = line.into();
+                        value
+                    }
+                    Err(e) => {
+                        summary.failed += 1;
+                        serde_json::json!({ "line": line, "error": e.to_json() })
+                    }
+                };
+                writeln!(output, "{}", value)?;
+                written += 1;
+            }
+        }
+        Ok(())
+    })?;
+
+    output.flush()?;
+    Ok(summary)
+}
+
+fn prove_record(
+    artifacts: &mut ProvingArtifacts,
+    checks: &TokenChecks,
+    record: &str,
+) -> Result<ZkArgsResponse> {
+    let request: ZkArgsRequest =
+        serde_json::from_str(record).map_err(|e| Error::InvalidRequest(e.to_string()))?;
+    let (token, pepper) = request.prepare(checks)?;
+    let zk_args = openid_zk_args(artifacts, &token, &pepper, false, &mut thread_rng())?;
+    ZkArgsResponse::new(&zk_args, request.format_version)
+}
+
+#[test]
+fn test_run_batch() {
+    use std::sync::Mutex;
+
+    let input = "1\n\n2\npanic\n30\nx\n4\n";
+    let mut output = vec![];
+    // The first record waits for the last, results must still come out in input order.
+    let (done_tx, done_rx) = mpsc::channel();
+    let done_rx = Mutex::new(done_rx);
+    let summary = run_batch(0u64, 3, input.as_bytes(), &mut output, |_, record| {
+        match record {
+            "panic" => panic!("record panicked"),
+            "1" => done_rx.lock().unwrap().recv().unwrap(),
+            "4" => done_tx.send(()).unwrap(),
+            _ => {}
+        }
+        let n: u64 = record
+            .parse()
+            .map_err(|_| Error::InvalidRequest(record.to_string()))?;
+        Ok(serde_json::json!({ "n": n }))
+    })
+    .unwrap();
+    assert_eq!(
+        summary,
+        BatchSummary {
+            proved: 4,
+            failed: 2
+        }
+    );
+
+    let lines: Vec<serde_json::Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let lines: Vec<_> = lines
+        .iter()
+        .map(|value| (value["line"].as_u64().unwrap(), value.get("n").is_some()))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            (1, true),
+            (3, true),
+            (4, false),
+            (5, true),
+            (6, false),
+            (7, true)
+        ]
+    );
+}
+
+#[test]
+fn test_run_batch_replaces_worker() {
+    let mut output = vec![];
+    let summary = run_batch(
+        0u64,
+        1,
+        "panic\n1\n".as_bytes(),
+        &mut output,
+        |n, record| {
+            *n += 1;
+            if record == "panic" {
+                panic!("record panicked");
+            }
+            Ok(serde_json::json!({ "n": *n }))
+        },
+    )
+    .unwrap();
+    assert_eq!(summary.failed, 1);
+    let output = String::from_utf8(output).unwrap();
+    let last: serde_json::Value = serde_json::from_str(output.lines().last().unwrap()).unwrap();
+    assert_eq!(last["n"], 1);
+}
@@ -0,0 +1,110 @@
+use crate::{
+    error::{Error, Result},
+    json::{self, ValueKind},
+    public_input::{
+        HEADER_BASE64_MAX_LEN, ID_TOKEN_MAX_LEN, PAYLOAD_BASE64_MAX_LEN, PAYLOAD_RAW_MAX_LEN,
+        SUB_MAX_LEN,
+    },
+    IdToken,
+};
+
+/// The longest inputs an `OpenIdCircuit` accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitLimits {
+    pub id_token: usize,
+    pub header_base64: usize,
+    pub payload_base64: usize,
+    pub payload_raw: usize,
+    pub sub: usize,
+}
+
+/// The limits of every proving key: `OpenIdCircuit::new` pads its inputs to fixed lengths, so
+/// the circuit, and with it these limits, is the same whatever the key.
+pub const CIRCUIT_LIMITS: CircuitLimits = CircuitLimits {
+    id_token: ID_TOKEN_MAX_LEN,
+    header_base64: HEADER_BASE64_MAX_LEN,
+    payload_base64: PAYLOAD_BASE64_MAX_LEN,
+    payload_raw: PAYLOAD_RAW_MAX_LEN,
+    sub: SUB_MAX_LEN,
+};
+
+impl CircuitLimits {
+    /// How far each input of `id_token` goes over these limits, as `(input, length, limit)`.
+    pub fn overflows(&self, id_token: &IdToken) -> Result<Vec<(&'static str, usize, usize)>> {
+        let mut parts = id_token.as_str().split('.');
+        let header_base64 = parts.next().map_or(0, str::len);
+        let payload_base64 = parts.next().map_or(0, str::len);
+        let sub = match json::find_member(&id_token.payload, "sub")? {
+            Some(member) if member.kind == ValueKind::String => {
+                member.value.right - member.value.left
+            }
+            Some(_) => return Err(Error::InvalidClaim("sub is not a String".to_string())),
+            None => return Err(Error::MissingClaim("sub".to_string())),
+        };
+
+        Ok([
+            ("id_token", id_token.as_str().len(), self.id_token),
+            ("header", header_base64, self.header_base64),
+            ("payload", payload_base64, self.payload_base64),
+            ("decoded payload", id_token.payload.len(), self.payload_raw),
+            ("sub", sub, self.sub),
+        ]
+        .into_iter()
+        .filter(|(_, len, limit)| len > limit)
+        .collect())
+    }
+}
+
+/// Make sure `id_token` fits the circuit before synthesizing.
+///
+/// No key fits a token that is too long, the error names the inputs that are over their limits.
+pub fn check_capacity(id_token: &IdToken) -> Result<()> {
+    let overflows = CIRCUIT_LIMITS.overflows(id_token)?;
+    if overflows.is_empty() {
+        return Ok(());
+    }
+    Err(Error::CircuitCapacityExceeded(format!(
+        "{}; the circuit's input lengths are fixed, so no proving key fits",
+        describe_overflows(&overflows)
+    )))
+}
+
+/// `input is len bytes, n over the limit of limit, ...` for the result of
+/// [`CircuitLimits::overflows`].
+fn describe_overflows(overflows: &[(&str, usize, usize)]) -> String {
+    overflows
+        .iter()
+        .map(|(input, len, limit)| {
+            format!(
+                "{} is {} bytes, {} over the limit of {}",
+                input,
+                len,
+                len - limit,
+                limit
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[test]
+fn test_check_capacity() {
+    use crate::id_token::test_token;
+
+    let id_token = |jti_len: usize| {
+        test_token(&format!(
+            r#"{{"iss":"https://idp","sub":"1","jti":"{}"}}"#,
+            "x".repeat(jti_len)
+        ))
+    };
+
+    assert!(check_capacity(&id_token(100)).is_ok());
+
+    let Err(Error::CircuitCapacityExceeded(msg)) = check_capacity(&id_token(1200)) else {
+        panic!("a 1200 byte jti fits");
+    };
+    assert!(msg.contains("payload is 1654 bytes, 118 over the limit of 1536"));
+    assert!(msg.contains("decoded payload is 1240 bytes, 88 over the limit of 1152"));
+    assert!(!msg.contains("header"));
+    assert!(msg.ends_with("no proving key fits"));
+}
@@ -0,0 +1,350 @@
+//! A trusted setup ceremony among a few parties, producing the same params as `gen-params`.
+//!
+//! `init` writes params with `tau = 1`, then every party multiplies `tau` by a secret `s`
+//! it throws away, so the final `tau` is unknown unless every party colludes. Each contribution
+//! is recorded in the transcript with `tau` in G1 and G2 after it, the party's public key
+//! `s * G1`, `s * G2`, a Schnorr proof of knowledge of `s`, and a hash chaining it to the
+//! previous entries. `verify` replays the chain with pairings and checks the final params
+//! against it.
+
+use plonk::{
+    ark_bn254::{Bn254, Fr, G1Affine, G1Projective, G2Affine},
+    ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve},
+    ark_ff::{Field, PrimeField, UniformRand, Zero},
+    ark_serialize::{CanonicalDeserialize, CanonicalSerialize},
+    kzg10::{PCKey, VKey},
+};
+use prover::utils::{from_0x_hex, to_0x_hex};
+use rand::RngCore;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{Error, Result},
+    import_params::check_powers,
+};
+
+/// Domain separation for the first transcript hash and the proofs of knowledge.
+const DOMAIN: &[u8] = b"unipass-openid-auth ceremony v1";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub k: u32,
+    pub contributions: Vec<Contribution>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Contribution {
+    pub name: String,
+    #[serde(with = "hex_point")]
+    pub tau_g1: G1Affine,
+    #[serde(with = "hex_point")]
+    pub tau_g2: G2Affine,
+    /// `s * G1`
+    #[serde(with = "hex_point")]
+    pub public_key_g1: G1Affine,
+    /// `s * G2`
+    #[serde(with = "hex_point")]
+    pub public_key_g2: G2Affine,
+    /// The Schnorr proof `(r * G1, r + c * s)` of knowing `s`.
+    #[serde(with = "hex_point")]
+    pub pok_commitment: G1Affine,
+    #[serde(with = "hex_point")]
+    pub pok_response: Fr,
+    /// `0x` hex `sha256` of the previous hash and this contribution.
+    pub hash: String,
+}
+
+impl Transcript {
+    /// The hash the next contribution chains to.
+    pub fn last_hash(&self) -> Result<[u8; 32]> {
+        match self.contributions.last() {
+            Some(contribution) => parse_hash(&contribution.hash),
+            None => Ok(Sha256::new()
+                .chain_update(DOMAIN)
+                .chain_update(self.k.to_le_bytes())
+                .finalize()
+                .into()),
+        }
+    }
+
+    /// `tau` in G1 and G2 after the last contribution.
+    fn last_tau(&self) -> (G1Affine, G2Affine) {
+        match self.contributions.last() {
+            Some(contribution) => (contribution.tau_g1, contribution.tau_g2),
+            None => (
+                G1Affine::prime_subgroup_generator(),
+                G2Affine::prime_subgroup_generator(),
+            ),
+        }
+    }
+}
+
+/// Params for circuits of up to `2^k` gates with `tau = 1`, and an empty transcript.
+pub fn init(k: u32) -> (PCKey<Bn254>, Transcript) {
+    let max_degree = 1usize << k;
+    let g = G1Affine::prime_subgroup_generator();
+    let h = G2Affine::prime_subgroup_generator();
+    let pckey = PCKey {
+        powers: vec![g; max_degree + 1],
+        max_degree,
+        vk: VKey {
+            g,
+            h,
+            beta_h: h,
+            max_degree,
+        },
+    };
+    let transcript = Transcript {
+        k,
+        contributions: vec![],
+    };
+    (pckey, transcript)
+}
+
+/// Multiply the `tau` of `pckey` by a fresh secret and record it in `transcript`.
+pub fn contribute<R: RngCore>(
+    pckey: &mut PCKey<Bn254>,
+    transcript: &mut Transcript,
+    name: &str,
+    rng: &mut R,
+) -> Result<()> {
+    let (tau_g1, tau_g2) = transcript.last_tau();
+    if pckey.powers.get(1) != Some(&tau_g1) || pckey.vk.beta_h != tau_g2 {
+        return Err(Error::ArtifactMismatch(
+            "the params are not the output of the last contribution".to_string(),
+        ));
+    }
+    let prev_hash = transcript.last_hash()?;
+
+    let s = Fr::rand(rng);
+    scale_powers(&mut pckey.powers, s);
+    pckey.vk.beta_h = pckey.vk.beta_h.mul(s).into_affine();
+
+    let g = G1Affine::prime_subgroup_generator();
+    let public_key_g1 = g.mul(s).into_affine();
+    let r = Fr::rand(rng);
+    let pok_commitment = g.mul(r).into_affine();
+    let c = pok_challenge(&prev_hash, &public_key_g1, &pok_commitment)?;
+    let mut contribution = Contribution {
+        name: name.to_string(),
+        tau_g1: pckey.powers[1],
+        tau_g2: pckey.vk.beta_h,
+        public_key_g1,
+        public_key_g2: G2Affine::prime_subgroup_generator().mul(s).into_affine(),
+        pok_commitment,
+        pok_response: r + c * s,
+        hash: String::new(),
+    };
+    contribution.hash = to_0x_hex(contribution_hash(&prev_hash, &contribution)?);
+    transcript.contributions.push(contribution);
+    Ok(())
+}
+
+/// Check every contribution of `transcript` and that `pckey` is the output of the last one.
+///
+/// A transcript without contributions is refused, its params have `tau = 1`.
+pub fn verify<R: RngCore>(
+    pckey: &PCKey<Bn254>,
+    transcript: &Transcript,
+    rng: &mut R,
+) -> Result<()> {
+    if transcript.contributions.is_empty() {
+        return Err(Error::ArtifactMismatch(
+            "the transcript has no contributions, so tau is 1".to_string(),
+        ));
+    }
+    let g = G1Affine::prime_subgroup_generator();
+    let h = G2Affine::prime_subgroup_generator();
+    let mut prev = Transcript {
+        k: transcript.k,
+        contributions: vec![],
+    };
+    for (i, contribution) in transcript.contributions.iter().enumerate() {
+        let fail = |msg: &str| {
+            Err(Error::ArtifactMismatch(format!(
+                "contribution {} ({}): {}",
+                i + 1,
+                contribution.name,
+                msg
+            )))
+        };
+        // A zero secret passes every check below and makes tau zero.
+        if contribution.public_key_g1.is_zero()
+            || contribution.public_key_g2.is_zero()
+            || contribution.tau_g1.is_zero()
+            || contribution.tau_g2.is_zero()
+        {
+            return fail("the secret is zero");
+        }
+        let prev_hash = prev.last_hash()?;
+        let (prev_g1, _) = prev.last_tau();
+
+        let c = pok_challenge(
+            &prev_hash,
+            &contribution.public_key_g1,
+            &contribution.pok_commitment,
+        )?;
+        if g.mul(contribution.pok_response)
+            != contribution.pok_commitment.into_projective() + contribution.public_key_g1.mul(c)
+        {
+            return fail("invalid proof of knowledge");
+        }
+        if Bn254::pairing(contribution.public_key_g1, h)
+            != Bn254::pairing(g, contribution.public_key_g2)
+        {
+            return fail("the G1 and G2 public keys differ");
+        }
+        if Bn254::pairing(contribution.tau_g1, h)
+            != Bn254::pairing(prev_g1, contribution.public_key_g2)
+        {
+            return fail("tau is not the previous tau times the secret");
+        }
+        if Bn254::pairing(contribution.tau_g1, h) != Bn254::pairing(g, contribution.tau_g2) {
+            return fail("tau differs in G1 and G2");
+        }
+        if contribution_hash(&prev_hash, contribution)? != parse_hash(&contribution.hash)? {
+            return fail("hash does not chain to the previous contribution");
+        }
+        prev.contributions.push(contribution.clone());
+    }
+
+    let (tau_g1, tau_g2) = prev.last_tau();
+    if pckey.max_degree != 1 << transcript.k
+        || pckey.powers.len() != pckey.max_degree + 1
+        || pckey.powers[1] != tau_g1
+        || pckey.vk.g != g
+        || pckey.vk.h != h
+        || pckey.vk.beta_h != tau_g2
+    {
+        return Err(Error::ArtifactMismatch(
+            "the params are not the output of the last contribution".to_string(),
+        ));
+    }
+    check_powers(&pckey.powers, &[h, tau_g2], rng)
+}
+
+/// Multiply `powers[i]` by `s^i`, on every core.
+fn scale_powers(powers: &mut [G1Affine], s: Fr) {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_len = powers.len().div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        for (i, chunk) in powers.chunks_mut(chunk_len).enumerate() {
+            scope.spawn(move || {
+                let mut power = s.pow([(i * chunk_len) as u64]);
+                let scaled: Vec<G1Projective> = chunk
+                    .iter()
+                    .map(|point| {
+                        let scaled = point.mul(power);
+                        power *= s;
+                        scaled
+                    })
+                    .collect();
+                chunk.copy_from_slice(&G1Projective::batch_normalization_into_affine(&scaled));
+            });
+        }
+    });
+}
+
+fn pok_challenge(prev_hash: &[u8], public_key: &G1Affine, commitment: &G1Affine) -> Result<Fr> {
+    let mut hasher = Sha256::new().chain_update(DOMAIN).chain_update(prev_hash);
+    for point in [public_key, commitment] {
+        hasher.update(point_bytes(point)?);
+    }
+    Ok(Fr::from_be_bytes_mod_order(&hasher.finalize()))
+}
+
+fn contribution_hash(prev_hash: &[u8], contribution: &Contribution) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new().chain_update(prev_hash);
+    hasher.update((contribution.name.len() as u64).to_le_bytes());
+    hasher.update(&contribution.name);
+    hasher.update(point_bytes(&contribution.tau_g1)?);
+    hasher.update(point_bytes(&contribution.tau_g2)?);
+    hasher.update(point_bytes(&contribution.public_key_g1)?);
+    hasher.update(point_bytes(&contribution.public_key_g2)?);
+    hasher.update(point_bytes(&contribution.pok_commitment)?);
+    hasher.update(point_bytes(&contribution.pok_response)?);
+    Ok(hasher.finalize().into())
+}
+
+fn point_bytes<T: CanonicalSerialize>(point: &T) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    point
+        .serialize(&mut bytes)
+        .map_err(|e| Error::InvalidArtifact(format!("ceremony transcript: {:?}", e)))?;
+    Ok(bytes)
+}
+
+fn parse_hash(hash: &str) -> Result<[u8; 32]> {
+    from_0x_hex(hash)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| Error::InvalidArtifact(format!("ceremony transcript: hash {}", hash)))
+}
+
+/// Serde for points and scalars as `0x` hex of their compressed serialization.
+mod hex_point {
+    use super::*;
+
+    pub fn serialize<T: CanonicalSerialize, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        let bytes = point_bytes(value).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&to_0x_hex(bytes))
+    }
+
+    pub fn deserialize<'de, T: CanonicalDeserialize, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<T, D::Error> {
+        let hex = <String as Deserialize>::deserialize(deserializer)?;
+        let bytes = from_0x_hex(&hex).map_err(serde::de::Error::custom)?;
+        T::deserialize(&*bytes).map_err(|e| serde::de::Error::custom(format!("{:?}", e)))
+    }
+}
+
+#[test]
+fn test_ceremony() {
+    let mut rng = rand::thread_rng();
+    let (mut pckey, mut transcript) = init(3);
+    assert!(matches!(
+        verify(&pckey, &transcript, &mut rng),
+        Err(Error::ArtifactMismatch(msg)) if msg.contains("no contributions")
+    ));
+    contribute(&mut pckey, &mut transcript, "alice", &mut rng).unwrap();
+    contribute(&mut pckey, &mut transcript, "bob", &mut rng).unwrap();
+    assert_ne!(pckey.powers[1], G1Affine::prime_subgroup_generator());
+
+    let json = serde_json::to_string(&transcript).unwrap();
+    let transcript: Transcript = serde_json::from_str(&json).unwrap();
+    verify(&pckey, &transcript, &mut rng).unwrap();
+
+    let mut forged = transcript.clone();
+    forged.contributions[0].name = "mallory".to_string();
+    assert!(matches!(
+        verify(&pckey, &forged, &mut rng),
+        Err(Error::ArtifactMismatch(msg)) if msg.contains("hash does not chain")
+    ));
+
+    let mut tampered = pckey.clone();
+    tampered.powers[2] = tampered.powers[3];
+    assert!(verify(&tampered, &transcript, &mut rng).is_err());
+
+    let mut zero = transcript.clone();
+    let contribution = &mut zero.contributions[1];
+    contribution.public_key_g1 = G1Affine::zero();
+    contribution.public_key_g2 = G2Affine::zero();
+    contribution.pok_commitment = G1Affine::prime_subgroup_generator()
+        .mul(contribution.pok_response)
+        .into_affine();
+    contribution.tau_g1 = G1Affine::zero();
+    contribution.tau_g2 = G2Affine::zero();
+    assert!(matches!(
+        verify(&pckey, &zero, &mut rng),
+        Err(Error::ArtifactMismatch(msg)) if msg.contains("secret is zero")
+    ));
+
+    let (mut other, _) = init(3);
+    let mut other_transcript = transcript.clone();
+    assert!(contribute(&mut other, &mut other_transcript, "carol", &mut rng).is_err());
+}
@@ -0,0 +1,280 @@
+use std::fmt::Write;
+
+use ethers::types::U256;
+use prover::utils::{from_0x_hex, to_0x_hex};
+use serde::Serialize;
+
+use crate::{
+    error::{Error, Result},
+    layout::FormatVersion,
+};
+
+/// The command a packed argument blob was produced by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ArgsKind {
+    /// `open-id-args`, written to `id_token.output`.
+    Direct,
+    /// `open-id-zk-args`, written to `id_token_zk.output`.
+    Zk,
+}
+
+/// A field of a packed argument blob and where it sits in the blob.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Field {
+    pub name: &'static str,
+    pub offset: usize,
+    pub len: usize,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Uint(u128),
+    Bytes {
+        hex: String,
+        /// The bytes as text, when they are printable ASCII.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    /// 32-byte words, `0x` hex encoded.
+    Words(Vec<String>),
+}
+
+/// Split the `encode_packed` output of `kind` in format `version` back into its fields.
+pub fn decode_args(kind: ArgsKind, version: FormatVersion, blob: &[u8]) -> Result<Vec<Field>> {
+    let mut reader = Reader {
+        blob,
+        offset: 0,
+        fields: vec![],
+    };
+    for &name in version.token_names() {
+        reader.uint(name, 4)?;
+    }
+    match kind {
+        ArgsKind::Direct => {
+            reader.bytes("header_len", "header")?;
+            reader.bytes("payload_len", "payload")?;
+            reader.bytes("signature_len", "signature")?;
+        }
+        ArgsKind::Zk => {
+            reader.uint("header_base64_len", 4)?;
+            reader.uint("payload_left_index", 4)?;
+            reader.uint("payload_base64_len", 4)?;
+            reader.bytes32("id_token_hash")?;
+            reader.bytes32("sub_pepper_hash")?;
+            reader.uint("domain_size", 16)?;
+            reader.bytes("header_len", "header")?;
+            reader.bytes("payload_pub_match_len", "payload_pub_match")?;
+            reader.bytes("signature_len", "signature")?;
+            reader.words("vk_data_len", "vk_data")?;
+            reader.words("public_inputs_len", "public_inputs")?;
+            reader.words("proof_data_len", "proof_data")?;
+        }
+    }
+
+    if reader.offset != blob.len() {
+        return Err(Error::InvalidArtifact(format!(
+            "{} trailing bytes at offset {}",
+            blob.len() - reader.offset,
+            reader.offset
+        )));
+    }
+    Ok(reader.fields)
+}
+
+/// The integer field called `name`.
+pub fn uint_field(fields: &[Field], name: &str) -> Result<u128> {
+    match find_field(fields, name)? {
+        Value::Uint(value) => Ok(*value),
+        _ => Err(Error::InvalidArtifact(format!(
+            "{} is not an integer",
+            name
+        ))),
+    }
+}
+
+/// The word list field called `name`.
+pub fn words_field(fields: &[Field], name: &str) -> Result<Vec<U256>> {
+    match find_field(fields, name)? {
+        Value::Words(words) => Ok(words
+            .iter()
+            .map(|word| {
+                U256::from_big_endian(&from_0x_hex(word).expect("hex encoded by the decoder"))
+            })
+            .collect()),
+        _ => Err(Error::InvalidArtifact(format!(
+            "{} is not a word list",
+            name
+        ))),
+    }
+}
+
+fn find_field<'a>(fields: &'a [Field], name: &str) -> Result<&'a Value> {
+    fields
+        .iter()
+        .find(|field| field.name == name)
+        .map(|field| &field.value)
+        .ok_or_else(|| Error::InvalidArtifact(format!("no field {}", name)))
+}
+
+/// Render decoded fields one per line as `offset length name: value`.
+pub fn render_tree(fields: &[Field]) -> String {
+    let mut tree = String::new();
+    for field in fields {
+        let _ = write!(
+            tree,
+            "{:#07x} {:>6}  {}",
+            field.offset, field.len, field.name
+        );
+        match &field.value {
+            Value::Uint(value) => {
+                let _ = writeln!(tree, ": {}", value);
+            }
+            Value::Bytes { hex, text } => {
+                let _ = writeln!(tree, ": {}", hex);
+                if let Some(text) = text {
+                    let _ = writeln!(tree, "{:15}└─ {}", "", text);
+                }
+            }
+            Value::Words(words) => {
+                let _ = writeln!(tree, ":");
+                for (i, word) in words.iter().enumerate() {
+                    let branch = if i + 1 == words.len() { '└' } else { '├' };
+                    let _ = writeln!(
+                        tree,
+                        "{:15}{}─ {:#07x} [{}] {}",
+                        "",
+                        branch,
+                        field.offset + i * 32,
+                        i,
+                        word
+                    );
+                }
+            }
+        }
+    }
+    tree
+}
+
+struct Reader<'a> {
+    blob: &'a [u8],
+    offset: usize,
+    fields: Vec<Field>,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, name: &str, len: usize) -> Result<&'a [u8]> {
+        let left = self.blob.len() - self.offset;
+        if left < len {
+            return Err(Error::InvalidArtifact(format!(
+                "{} at offset {} needs {} bytes, {} left",
+                name, self.offset, len, left
+            )));
+        }
+        let bytes = &self.blob[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn push(&mut self, name: &'static str, len: usize, value: Value) {
+        self.fields.push(Field {
+            name,
+            offset: self.offset - len,
+            len,
+            value,
+        });
+    }
+
+    /// A big-endian unsigned integer of `size` bytes.
+    fn uint(&mut self, name: &'static str, size: usize) -> Result<u128> {
+        let value = self
+            .take(name, size)?
+            .iter()
+            .fold(0u128, |value, byte| value << 8 | *byte as u128);
+        self.push(name, size, Value::Uint(value));
+        Ok(value)
+    }
+
+    fn bytes32(&mut self, name: &'static str) -> Result<()> {
+        let bytes = self.take(name, 32)?;
+        self.push(name, 32, bytes_value(bytes));
+        Ok(())
+    }
+
+    /// A `uint32` length prefix followed by that many bytes.
+    fn bytes(&mut self, len_name: &'static str, name: &'static str) -> Result<()> {
+        let len = self.uint(len_name, 4)? as usize;
+        let bytes = self.take(name, len)?;
+        self.push(name, len, bytes_value(bytes));
+        Ok(())
+    }
+
+    /// A `uint32` count prefix followed by that many 32-byte words.
+    fn words(&mut self, len_name: &'static str, name: &'static str) -> Result<()> {
+        let count = self.uint(len_name, 4)? as usize;
+        let len = count
+            .checked_mul(32)
+            .ok_or_else(|| Error::InvalidArtifact(format!("{} count {}", name, count)))?;
+        let bytes = self.take(name, len)?;
+        let words = bytes.chunks(32).map(to_0x_hex).collect();
+        self.push(name, len, Value::Words(words));
+        Ok(())
+    }
+}
+
+fn bytes_value(bytes: &[u8]) -> Value {
+    let printable = !bytes.is_empty() && bytes.iter().all(|b| (0x20..0x7f).contains(b));
+    Value::Bytes {
+        hex: to_0x_hex(bytes),
+        text: printable.then(|| String::from_utf8_lossy(bytes).into_owned()),
+    }
+}
+
+#[test]
+fn test_decode_args() {
+    use crate::{
+        id_token::test_token, openid_args::openid_args, openid_zk_args::test_zk_args,
+        policy::TokenChecks,
+    };
+
+    let payload = r#"{"iss":"https://idp","sub":"1","aud":"a","nonce":"n","iat":1000,"exp":2000}"#;
+    let id_token = test_token(payload);
+    let token = TokenChecks::default()
+        .check(id_token.clone(), None)
+        .unwrap();
+    let layout = *token.layout();
+
+    let blob = openid_args(&token, FormatVersion::V1).unwrap();
+    let fields = decode_args(ArgsKind::Direct, FormatVersion::V1, &blob).unwrap();
+    let field = |fields: &[Field], name| fields.iter().find(|f| f.name == name).cloned().unwrap();
+    assert_eq!(
+        field(&fields, "sub_left_index").value,
+        Value::Uint(layout.sub_left_index as u128)
+    );
+    let payload_field = field(&fields, "payload");
+    assert_eq!(payload_field.offset, 11 * 4 + 4 + 26 + 4);
+    assert!(
+        matches!(payload_field.value, Value::Bytes { text: Some(text), .. } if text == payload)
+    );
+    assert_eq!(fields.last().unwrap().offset + 3, blob.len());
+    assert!(decode_args(ArgsKind::Direct, FormatVersion::V1, &blob[..blob.len() - 1]).is_err());
+    assert!(decode_args(ArgsKind::Zk, FormatVersion::V1, &blob).is_err());
+
+    let zk_args = test_zk_args(&token);
+    let blob = zk_args.encode_packed(FormatVersion::V2).unwrap();
+    let fields = decode_args(ArgsKind::Zk, FormatVersion::V2, &blob).unwrap();
+    assert_eq!(
+        field(&fields, "exp_right_index").value,
+        Value::Uint(layout.exp_right_index as u128)
+    );
+    assert_eq!(field(&fields, "domain_size").value, Value::Uint(1 << 10));
+    let Value::Words(vk_data) = field(&fields, "vk_data").value else {
+        panic!("vk_data is not a word list");
+    };
+    assert_eq!(vk_data.len(), 3);
+    assert_eq!(
+        field(&fields, "proof_data").value,
+        Value::Words(vec![to_0x_hex([&[0; 31][..], &[9]].concat()); 2])
+    );
+}
@@ -0,0 +1,179 @@
+use std::{any::Any, fmt};
+
+/// Everything that can go wrong while turning an id_token into contract arguments.
+///
+/// Failures without a dedicated variant are carried as [`Error::Other`].
+#[derive(Debug)]
+pub enum Error {
+    /// The id_token is not a well formed `Header.Payload.Signature` JWT with JSON parts.
+    MalformedJwt(String),
+    /// A claim the contracts need is absent from the header or payload.
+    MissingClaim(String),
+    /// A claim is present but has the wrong type or an unexpected value.
+    InvalidClaim(String),
+    /// The pepper is not a 32-byte hex string.
+    InvalidPepper(String),
+    /// The id_token signature does not verify.
+    InvalidSignature(String),
+    /// A JWK is missing, malformed or unusable for the token.
+    InvalidKey(String),
+    /// The token breaks a rule of the claim validation policy.
+    PolicyViolation {
+        rule: &'static str,
+        detail: String,
+    },
+    /// A `serve` request body or `prove-batch` record is not a valid request.
+    InvalidRequest(String),
+    /// A params, proving key, verifier comms or proof file could not be decoded.
+    InvalidArtifact(String),
+    /// Artifacts that do not belong together were combined.
+    ArtifactMismatch(String),
+    /// The id_token does not fit in the circuit of the loaded proving key.
+    CircuitCapacityExceeded(String),
+    /// The prover failed to synthesize or prove the circuit.
+    Prover(String),
+    /// A proof was checked and rejected.
+    VerificationFailed,
+    /// The circuit's public input disagrees with its off-circuit recomputation.
+    PublicInputMismatch(String),
+    Io(std::io::Error),
+    Other(anyhow::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// A stable snake case name for machine readable error output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::MalformedJwt(_) => "malformed_jwt",
+            Error::MissingClaim(_) => "missing_claim",
+            Error::InvalidClaim(_) => "invalid_claim",
+            Error::InvalidPepper(_) => "invalid_pepper",
+            Error::InvalidSignature(_) => "invalid_signature",
+            Error::InvalidKey(_) => "invalid_key",
+            Error::PolicyViolation { .. } => "policy_violation",
+            Error::InvalidRequest(_) => "invalid_request",
+            Error::InvalidArtifact(_) => "invalid_artifact",
+            Error::ArtifactMismatch(_) => "artifact_mismatch",
+            Error::CircuitCapacityExceeded(_) => "circuit_capacity_exceeded",
+            Error::Prover(_) => "prover",
+            Error::VerificationFailed => "verification_failed",
+            Error::PublicInputMismatch(_) => "public_input_mismatch",
+            Error::Io(_) => "io",
+            Error::Other(_) => "other",
+        }
+    }
+
+    /// An I/O error on `path`, with the path in its message.
+    pub fn io_at(path: &str, e: std::io::Error) -> Self {
+        Error::Io(std::io::Error::new(e.kind(), format!("{}: {}", path, e)))
+    }
+
+    /// The `{"error", "message", "exit_code"}` object the CLI and the prover service report.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "error": self.kind(),
+            "message": self.to_string(),
+            "exit_code": self.exit_code(),
+        })
+    }
+
+    /// The error of a proof that panicked, with the panic message.
+    pub fn from_panic(panic: Box<dyn Any + Send>) -> Self {
+        let msg = match panic.downcast::<String>() {
+            Ok(msg) => *msg,
+            Err(panic) => panic
+                .downcast_ref::<&str>()
+                .map_or("unknown panic", |msg| msg)
+                .to_string(),
+        };
+        Error::Prover(format!("panicked: {}", msg))
+    }
+
+    /// The process exit code the CLI reports this error with.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Other(_) => 1,
+            Error::MalformedJwt(_) => 10,
+            Error::MissingClaim(_) => 11,
+            Error::InvalidClaim(_) => 12,
+            Error::InvalidPepper(_) => 13,
+            Error::InvalidSignature(_) => 14,
+            Error::InvalidKey(_) => 15,
+            Error::PolicyViolation { .. } => 16,
+            Error::InvalidRequest(_) => 17,
+            Error::InvalidArtifact(_) => 20,
+            Error::ArtifactMismatch(_) => 21,
+            Error::CircuitCapacityExceeded(_) => 22,
+            Error::Prover(_) => 23,
+            Error::VerificationFailed => 24,
+            Error::PublicInputMismatch(_) => 25,
+            Error::Io(_) => 30,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MalformedJwt(msg) => write!(f, "malformed id_token: {}", msg),
+            Error::MissingClaim(name) => write!(f, "missing claim {}", name),
+            Error::InvalidClaim(msg) => write!(f, "invalid claim: {}", msg),
+            Error::InvalidPepper(msg) => write!(f, "invalid pepper: {}", msg),
+            Error::InvalidSignature(msg) => write!(f, "invalid signature: {}", msg),
+            Error::InvalidKey(msg) => write!(f, "invalid key: {}", msg),
+            Error::PolicyViolation { rule, detail } => {
+                write!(f, "policy rule {} failed: {}", rule, detail)
+            }
+            Error::InvalidRequest(msg) => write!(f, "invalid request: {}", msg),
+            Error::InvalidArtifact(msg) => write!(f, "invalid artifact: {}", msg),
+            Error::ArtifactMismatch(msg) => write!(f, "artifact mismatch: {}", msg),
+            Error::CircuitCapacityExceeded(msg) => write!(f, "circuit capacity exceeded: {}", msg),
+            Error::Prover(msg) => write!(f, "prover error: {}", msg),
+            Error::VerificationFailed => write!(f, "verification failed"),
+            Error::PublicInputMismatch(components) => {
+                write!(f, "public input mismatch in {}", components)
+            }
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::Other(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<Error>() {
+            Ok(e) => e,
+            Err(e) => Error::Other(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Other(e.into())
+    }
+}
+
+impl From<ethers::abi::EncodePackedError> for Error {
+    fn from(e: ethers::abi::EncodePackedError) -> Self {
+        Error::Other(e.into())
+    }
+}
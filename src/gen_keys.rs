@@ -1,28 +1,146 @@
+use base64::Engine;
 use plonk::{
     ark_bn254::{Bn254, Fr},
-    prover::Prover,
+    kzg10::{Commitment, PCKey},
+    prover::{Prover, ProverKey},
     GeneralEvaluationDomain,
 };
-use prover::{
-    circuit::openid::OpenIdCircuit,
-    parameters::{load_params, store_prover_key, store_verifier_comms},
+use prover::circuit::openid::OpenIdCircuit;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    artifacts::OpenIdProver,
+    error::{Error, Result},
+    IdToken,
 };
 
-pub fn gen_keys(params_path: String, id_token_path: String, pk_path: String, vc_path: String) {
-    let pckey = load_params(&params_path).unwrap();
-    let pepper = "03ac674216f3e15c761ee1a5e255f067953623c8b388b4459e13f978d7c846f4";
-    let from_pepper = hex::decode(pepper).unwrap();
-    let id_token = std::fs::read_to_string(&id_token_path).unwrap();
-    let circuit = OpenIdCircuit::new(&id_token, &from_pepper);
-    let mut cs = circuit.synthesize();
+/// The pepper only shapes the circuit, any 32-byte value gives the same keys.
+const KEYGEN_PEPPER: [u8; 32] = [0; 32];
+
+/// The shape of the synthetic id_token `gen-keys` builds the circuit from.
+///
+/// The circuit pads every input to its fixed maximum length, so the keys are the same for any
+/// token that fits, synthetic or real, and the shape only has to make a token the circuit takes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenTemplate {
+    /// Payload claims, in order. `sub` is required.
+    pub claims: Vec<String>,
+    /// Length of `sub`.
+    pub sub_len: usize,
+}
+
+impl Default for TokenTemplate {
+    fn default() -> Self {
+        Self {
+            claims: ["iss", "sub", "aud", "iat", "exp", "nonce"]
+                .map(String::from)
+                .to_vec(),
+            sub_len: 32,
+        }
+    }
+}
 
-    let pk = cs
-        .compute_prover_key::<GeneralEvaluationDomain<Fr>>()
-        .unwrap();
+impl TokenTemplate {
+    /// A token of this shape with placeholder claims and an all-zero RS256 signature.
+    pub fn id_token(&self) -> Result<IdToken> {
+        if !self.claims.iter().any(|claim| claim == "sub") {
+            return Err(Error::MissingClaim("sub".to_string()));
+        }
+
+        let header = r#"{"alg":"RS256","typ":"JWT","kid":"synthetic"}"#;
+        let claims: Vec<_> = self
+            .claims
+            .iter()
+            .map(|claim| {
+                let value = match claim.as_str() {
+                    "sub" => format!(r#""{}""#, "0".repeat(self.sub_len)),
+                    "iss" => r#""https://issuer.invalid""#.to_string(),
+                    "iat" | "nbf" => "1700000000".to_string(),
+                    "exp" => "1700003600".to_string(),
+                    _ => format!(r#""synthetic-{}""#, claim),
+                };
+                format!(r#""{}":{}"#, claim, value)
+            })
+            .collect();
+        let payload = format!("{{{}}}", claims.join(","));
+
+        let base64url_engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        IdToken::parse(&format!(
+            "{}.{}.{}",
+            base64url_engine.encode(header),
+            base64url_engine.encode(payload),
+            base64url_engine.encode([0u8; 256])
+        ))
+    }
+}
+
+/// Compute the proving key of the OpenID circuit shaped by `id_token`.
+pub fn gen_prover_key(id_token: &IdToken) -> Result<ProverKey<Fr>> {
+    let circuit = OpenIdCircuit::new(id_token.as_str(), &KEYGEN_PEPPER);
+    let mut cs = circuit.synthesize();
 
-    store_prover_key(&pk, &pk_path).unwrap();
+    cs.compute_prover_key::<GeneralEvaluationDomain<Fr>>()
+        .map_err(|e| Error::Prover(format!("failed to compute proving key: {:?}", e)))
+}
 
+/// Commit to the proving key, returning the prover together with its verifier comms.
+pub fn gen_verifier_comms(
+    pckey: &PCKey<Bn254>,
+    pk: ProverKey<Fr>,
+) -> (OpenIdProver, Vec<Commitment<Bn254>>) {
     let mut prover = Prover::<Fr, GeneralEvaluationDomain<Fr>, Bn254>::new(pk);
-    let verifier_comms = prover.init_comms(&pckey);
-    store_verifier_comms(&verifier_comms, &vc_path).unwrap();
+    let verifier_comms = prover.init_comms(pckey);
+    (prover, verifier_comms)
+}
+
+/// How many commitments a proof made by `prover` carries, counted on a proof of `id_token`.
+///
+/// `proof_data` holds the commitments and evaluations without their counts, and the count is
+/// the same for every proof of the circuit.
+pub fn count_proof_commitments<R: RngCore>(
+    pckey: &PCKey<Bn254>,
+    prover: &mut OpenIdProver,
+    id_token: &IdToken,
+    rng: &mut R,
+) -> Result<usize> {
+    let circuit = OpenIdCircuit::new(id_token.as_str(), &KEYGEN_PEPPER);
+    let mut cs = circuit.synthesize();
+    let proof = prover
+        .prove(&mut cs, pckey, rng)
+        .map_err(|e| Error::Prover(format!("{:?}", e)))?;
+    Ok(proof.commitments.len())
+}
+
+#[test]
+fn test_token_template() {
+    use crate::ClaimLayout;
+
+    let template: TokenTemplate = serde_json::from_str(r#"{"sub_len": 40}"#).unwrap();
+    let id_token = template.id_token().unwrap();
+    let layout = ClaimLayout::locate(&id_token, None).unwrap();
+    assert_eq!(layout.sub_right_index - layout.sub_left_index, 40);
+    assert!(serde_json::from_str::<TokenTemplate>(r#"{"payload_len": 400}"#).is_err());
+
+    let template = TokenTemplate {
+        claims: vec!["iss".to_string()],
+        ..Default::default()
+    };
+    assert!(matches!(template.id_token(), Err(Error::MissingClaim(_))));
+}
+
+#[test]
+#[ignore = "computes two proving keys of the full circuit"]
+fn test_template_circuit_id() {
+    use crate::{header::circuit_id, id_token::test_token};
+
+    let template = TokenTemplate::default().id_token().unwrap();
+    let token = test_token(
+        r#"{"iss":"https://accounts.google.com","aud":"407408718192.apps.googleusercontent.com","sub":"105028784556506816441","nonce":"n-0S6_WzA2Mj","iat":1694678493,"exp":1694682093}"#,
+    );
+    assert_eq!(
+        circuit_id(&gen_prover_key(&template).unwrap()).unwrap(),
+        circuit_id(&gen_prover_key(&token).unwrap()).unwrap()
+    );
 }
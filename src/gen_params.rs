@@ -1,11 +1,44 @@
-use plonk::ark_bn254::Bn254;
-use prover::parameters::{prepare_generic_params, store_params};
-use rand::thread_rng;
+use plonk::{ark_bn254::Bn254, kzg10::PCKey};
+use prover::parameters::prepare_generic_params;
+use rand::RngCore;
 
-pub fn gen_params(k: u32, params_path: String) {
-    let mut rng = thread_rng();
+use crate::error::{Error, Result};
+
+/// Generate a random SRS for circuits of up to `2^k` gates.
+pub fn gen_params<R: RngCore>(k: u32, rng: &mut R) -> PCKey<Bn254> {
     let n: usize = 1 << k;
     // prepare SRS
-    let pckey = prepare_generic_params::<Bn254>(n, &mut rng);
-    store_params(&pckey, &params_path).unwrap();
+    prepare_generic_params::<Bn254>(n, rng)
+}
+
+/// The SRS for circuits of up to `2^k` gates that `pckey` contains: the same `tau`, so its
+/// first powers and G2 elements, with the powers past `2^k` dropped.
+pub fn truncate_params(pckey: &PCKey<Bn254>, k: u32) -> Result<PCKey<Bn254>> {
+    let max_degree = 1usize << k;
+    if max_degree > pckey.max_degree || max_degree >= pckey.powers.len() {
+        return Err(Error::InvalidArtifact(format!(
+            "params of max degree {} cannot be truncated to 2^{}",
+            pckey.max_degree, k
+        )));
+    }
+    let mut vk = pckey.vk.clone();
+    vk.max_degree = max_degree;
+    Ok(PCKey {
+        powers: pckey.powers[..=max_degree].to_vec(),
+        max_degree,
+        vk,
+    })
+}
+
+#[test]
+fn test_truncate_params() {
+    let mut rng = rand::thread_rng();
+    let (mut pckey, mut transcript) = crate::ceremony::init(4);
+    crate::ceremony::contribute(&mut pckey, &mut transcript, "alice", &mut rng).unwrap();
+
+    let small = truncate_params(&pckey, 2).unwrap();
+    assert_eq!(small.powers, pckey.powers[..5]);
+    assert_eq!(small.max_degree, 4);
+    assert_eq!(small.vk.beta_h, pckey.vk.beta_h);
+    assert!(truncate_params(&pckey, 5).is_err());
 }
@@ -0,0 +1,366 @@
+//! The header in front of every params, proving key, verifier comms, verifier and proof file.
+//!
+//! It records which SRS and circuit an artifact belongs to, so that files from different
+//! `gen-params` or `gen-keys` runs are caught when they are combined. Files without a header,
+//! written before it existed, still load but are not checked.
+//!
+//! Since format version 2 the params, proving key and verifier comms bodies are stored
+//! uncompressed, about twice the size, because only uncompressed points can be deserialized
+//! without checks. One whose `sha256` matches a [`TrustedChecksums`] entry is deserialized that
+//! way, after hashing the whole file.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Seek, Write},
+};
+
+use plonk::{
+    ark_bn254::Fr,
+    ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError},
+    prover::ProverKey,
+};
+use prover::utils::to_0x_hex;
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+
+/// The first bytes of every artifact with a header.
+pub const MAGIC: [u8; 4] = *b"UPOA";
+/// The version of the header layout, 1 for artifacts with compressed bodies.
+pub const FORMAT_VERSION: u16 = 2;
+/// Magic, little endian version, kind, then the circuit id, SRS hash and vk hash.
+pub const HEADER_LEN: usize = 4 + 2 + 1 + 3 * 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    Params = 1,
+    ProvingKey = 2,
+    VerifierComms = 3,
+    Verifier = 4,
+    Proof = 5,
+}
+
+impl ArtifactKind {
+    fn from_u8(kind: u8) -> Option<Self> {
+        [
+            Self::Params,
+            Self::ProvingKey,
+            Self::VerifierComms,
+            Self::Verifier,
+            Self::Proof,
+        ]
+        .into_iter()
+        .find(|k| *k as u8 == kind)
+    }
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Params => "params",
+            Self::ProvingKey => "proving key",
+            Self::VerifierComms => "verifier comms",
+            Self::Verifier => "verifier",
+            Self::Proof => "proof",
+        })
+    }
+}
+
+/// What an artifact belongs to. Params have no circuit, their circuit id and vk hash are zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArtifactHeader {
+    pub version: u16,
+    pub kind: ArtifactKind,
+    /// `sha256` of the serialized proving key.
+    pub circuit_id: [u8; 32],
+    /// `sha256` of the SRS.
+    pub srs_hash: [u8; 32],
+    /// `sha256` of the `vkdata` the contracts are configured with.
+    pub vk_hash: [u8; 32],
+}
+
+impl ArtifactHeader {
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[..4].copy_from_slice(&MAGIC);
+        bytes[4..6].copy_from_slice(&self.version.to_le_bytes());
+        bytes[6] = self.kind as u8;
+        bytes[7..39].copy_from_slice(&self.circuit_id);
+        bytes[39..71].copy_from_slice(&self.srs_hash);
+        bytes[71..].copy_from_slice(&self.vk_hash);
+        bytes
+    }
+
+    /// Split `bytes` read from `path` into the header of a `kind` artifact and the body, or
+    /// `None` for an artifact without a header.
+    pub fn split<'a>(
+        bytes: &'a [u8],
+        kind: ArtifactKind,
+        path: &str,
+    ) -> Result<Option<(Self, &'a [u8])>> {
+        if !bytes.starts_with(&MAGIC) {
+            return Ok(None);
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::InvalidArtifact(format!(
+                "{} {} header is {} bytes, expected {}",
+                kind,
+                path,
+                bytes.len(),
+                HEADER_LEN
+            )));
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if !(1..=FORMAT_VERSION).contains(&version) {
+            return Err(Error::InvalidArtifact(format!(
+                "{} {} has format version {}, expected at most {}",
+                kind, path, version, FORMAT_VERSION
+            )));
+        }
+        match ArtifactKind::from_u8(bytes[6]) {
+            Some(found) if found == kind => {}
+            Some(found) => {
+                return Err(Error::InvalidArtifact(format!(
+                    "{} is a {}, expected a {}",
+                    path, found, kind
+                )))
+            }
+            None => {
+                return Err(Error::InvalidArtifact(format!(
+                    "{} {} has unknown artifact kind {}",
+                    kind, path, bytes[6]
+                )))
+            }
+        }
+        let hash = |i: usize| -> [u8; 32] { bytes[i..i + 32].try_into().unwrap() };
+        let header = Self {
+            version,
+            kind,
+            circuit_id: hash(7),
+            srs_hash: hash(39),
+            vk_hash: hash(71),
+        };
+        Ok(Some((header, &bytes[HEADER_LEN..])))
+    }
+
+    /// Prepend the header to `body`.
+    pub fn wrap(&self, body: &[u8]) -> Vec<u8> {
+        [&self.to_bytes()[..], body].concat()
+    }
+
+    /// Check the header `found` in `path` against this one, naming the first field that differs.
+    pub fn check(&self, found: &Self, path: &str) -> Result<()> {
+        for (field, expected, found) in [
+            ("SRS hash", self.srs_hash, found.srs_hash),
+            ("circuit id", self.circuit_id, found.circuit_id),
+            ("vk hash", self.vk_hash, found.vk_hash),
+        ] {
+            if expected != found {
+                return Err(Error::ArtifactMismatch(format!(
+                    "{} {} has {} {}, expected {}",
+                    self.kind,
+                    path,
+                    field,
+                    to_0x_hex(found),
+                    to_0x_hex(expected)
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The circuit id of `pk`.
+pub fn circuit_id(pk: &ProverKey<Fr>) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    pk.serialize(&mut hasher)
+        .map_err(|e| Error::InvalidArtifact(format!("proving key: {:?}", e)))?;
+    Ok(hasher.finalize().into())
+}
+
+/// `hash` as a header field, zero if it is not a `sha256`.
+pub fn hash_field(hash: &[u8]) -> [u8; 32] {
+    hash.try_into().unwrap_or_default()
+}
+
+/// `sha256sum` output naming the artifacts that are loaded without checking their points.
+///
+/// A corrupted or forged artifact with a trusted checksum is refused before it is deserialized,
+/// so only take the checksums from wherever the artifacts were generated.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedChecksums(HashMap<String, [u8; 32]>);
+
+impl TrustedChecksums {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut checksums = HashMap::new();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let invalid = || Error::InvalidArtifact(format!("trusted checksum line {:?}", line));
+            let (checksum, path) = line.split_once(' ').ok_or_else(invalid)?;
+            // `sha256sum` marks the path with `*` in binary mode and ` ` in text mode.
+            let path = path.strip_prefix([' ', '*']).ok_or_else(invalid)?;
+            let checksum = hex::decode(checksum)
+                .ok()
+                .and_then(|checksum| checksum.try_into().ok())
+                .ok_or_else(invalid)?;
+            checksums.insert(normalize(path).to_string(), checksum);
+        }
+        Ok(Self(checksums))
+    }
+
+    pub fn get(&self, path: &str) -> Option<&[u8; 32]> {
+        self.0.get(normalize(path))
+    }
+}
+
+fn normalize(path: &str) -> &str {
+    path.trim_start_matches("./")
+}
+
+/// Write `value` to `path` behind `header`, uncompressed so it can be loaded without checks.
+///
+/// The artifact is written to a temporary file next to `path` and renamed over it, so a process
+/// loading `path` never reads a half-written file.
+pub fn store_artifact<T: CanonicalSerialize>(
+    path: &str,
+    header: &ArtifactHeader,
+    value: &T,
+) -> Result<()> {
+    let invalid =
+        |e: &dyn fmt::Debug| Error::InvalidArtifact(format!("{} {}: {:?}", header.kind, path, e));
+    let tmp_path = format!("{}.{}.tmp", path, std::process::id());
+    let io = |e| Error::io_at(&tmp_path, e);
+    let write = || -> Result<()> {
+        let mut file = BufWriter::new(File::create(&tmp_path).map_err(io)?);
+        file.write_all(&header.to_bytes()).map_err(io)?;
+        value
+            .serialize_uncompressed(&mut file)
+            .map_err(|e| invalid(&e))?;
+        let file = file.into_inner().map_err(|e| io(e.into_error()))?;
+        file.sync_all().map_err(io)?;
+        fs::rename(&tmp_path, path).map_err(io)
+    };
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })
+}
+
+/// Read a `kind` artifact from `path` with its header, or with `load_legacy` if it has none.
+///
+/// With a `trusted` checksum, the file must match it and its points are not checked.
+pub fn load_artifact<T: CanonicalDeserialize>(
+    path: &str,
+    kind: ArtifactKind,
+    trusted: Option<&[u8; 32]>,
+    load_legacy: impl FnOnce(&str) -> std::result::Result<T, SerializationError>,
+) -> Result<(T, Option<ArtifactHeader>)> {
+    let invalid =
+        |e: &dyn fmt::Debug| Error::InvalidArtifact(format!("{} {}: {:?}", kind, path, e));
+    let io = |e| Error::io_at(path, e);
+    let mut file = File::open(path).map_err(io)?;
+
+    if let Some(trusted) = trusted {
+        let mut hasher = Sha256::new();
+        io::copy(&mut file, &mut hasher).map_err(io)?;
+        let checksum: [u8; 32] = hasher.finalize().into();
+        if checksum != *trusted {
+            return Err(Error::ArtifactMismatch(format!(
+                "{} {} has checksum {}, trusted {}",
+                kind,
+                path,
+                hex::encode(checksum),
+                hex::encode(trusted)
+            )));
+        }
+        file.rewind().map_err(io)?;
+    }
+    let mut reader = BufReader::new(file);
+    let mut bytes = Vec::with_capacity(HEADER_LEN);
+    (&mut reader)
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut bytes)
+        .map_err(io)?;
+    let Some((header, _)) = ArtifactHeader::split(&bytes, kind, path)? else {
+        drop(reader);
+        let value = load_legacy(path).map_err(|e| invalid(&e))?;
+        return Ok((value, None));
+    };
+    let value = match (header.version, trusted) {
+        (1, _) => T::deserialize(&mut reader),
+        (_, Some(_)) => T::deserialize_unchecked(&mut reader),
+        (_, None) => T::deserialize_uncompressed(&mut reader),
+    }
+    .map_err(|e| invalid(&e))?;
+    Ok((value, Some(header)))
+}
+
+#[test]
+fn test_artifact_header() {
+    let header = ArtifactHeader {
+        version: FORMAT_VERSION,
+        kind: ArtifactKind::ProvingKey,
+        circuit_id: [1; 32],
+        srs_hash: [2; 32],
+        vk_hash: [3; 32],
+    };
+    let bytes = header.wrap(b"body");
+    assert_eq!(
+        ArtifactHeader::split(&bytes, ArtifactKind::ProvingKey, "app.pk").unwrap(),
+        Some((header, &b"body"[..]))
+    );
+    assert_eq!(
+        ArtifactHeader::split(b"legacy", ArtifactKind::ProvingKey, "app.pk").unwrap(),
+        None
+    );
+    assert!(matches!(
+        ArtifactHeader::split(&bytes, ArtifactKind::Proof, "app.pk"),
+        Err(Error::InvalidArtifact(_))
+    ));
+
+    let other = ArtifactHeader {
+        srs_hash: [4; 32],
+        ..header
+    };
+    let Err(Error::ArtifactMismatch(msg)) = header.check(&other, "app.pk") else {
+        panic!("SRS hashes differ");
+    };
+    assert!(msg.starts_with("proving key app.pk has SRS hash 0x0404"));
+    assert!(header.check(&header, "app.pk").is_ok());
+}
+
+#[test]
+fn test_load_trusted_artifact() {
+    let path = std::env::temp_dir().join(format!("upoa-trusted-{}.bin", std::process::id()));
+    let path = path.to_str().unwrap();
+    let header = ArtifactHeader {
+        version: FORMAT_VERSION,
+        kind: ArtifactKind::VerifierComms,
+        circuit_id: [1; 32],
+        srs_hash: [2; 32],
+        vk_hash: [3; 32],
+    };
+    let value = vec![Fr::from(7u64), Fr::from(8u64)];
+    store_artifact(path, &header, &value).unwrap();
+    assert!(!std::path::Path::new(&format!("{}.{}.tmp", path, std::process::id())).exists());
+    let checksum = hex::encode(Sha256::digest(std::fs::read(path).unwrap()));
+    let legacy = |_: &str| -> std::result::Result<Vec<Fr>, _> { panic!("has a header") };
+
+    let trusted = TrustedChecksums::parse(&format!("{}  ./{}\n", checksum, path)).unwrap();
+    let loaded = load_artifact(path, header.kind, trusted.get(path), legacy).unwrap();
+    assert_eq!(loaded, (value.clone(), Some(header)));
+    let loaded = load_artifact(path, header.kind, None, legacy).unwrap();
+    assert_eq!(loaded, (value, Some(header)));
+
+    let forged = TrustedChecksums::parse(&format!("{} *{}", "00".repeat(32), path)).unwrap();
+    assert!(matches!(
+        load_artifact(path, header.kind, forged.get(path), legacy),
+        Err(Error::ArtifactMismatch(_))
+    ));
+    assert!(TrustedChecksums::parse("abc app.pk").is_err());
+    std::fs::remove_file(path).unwrap();
+    assert!(matches!(
+        load_artifact(path, header.kind, None, legacy),
+        Err(Error::Io(_))
+    ));
+}
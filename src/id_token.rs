@@ -0,0 +1,65 @@
+use base64::Engine;
+
+use crate::error::{Error, Result};
+
+/// An OpenID IdToken split into its decoded `Header.Payload.Signature` parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdToken {
+    raw: String,
+    pub header: Vec<u8>,
+    pub payload: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl IdToken {
+    pub fn parse(id_token: &str) -> Result<Self> {
+        let raw = id_token.trim();
+        let id_token_split: Vec<_> = raw.split('.').collect();
+        if id_token_split.len() != 3 {
+            return Err(Error::MalformedJwt(format!(
+                "expected 3 parts, found {}",
+                id_token_split.len()
+            )));
+        }
+        let base64url_engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let decode = |part: &str, name: &str| {
+            base64url_engine
+                .decode(part.as_bytes())
+                .map_err(|e| Error::MalformedJwt(format!("invalid base64url {}: {}", name, e)))
+        };
+        let header = decode(id_token_split[0], "header")?;
+        let payload = decode(id_token_split[1], "payload")?;
+        let signature = decode(id_token_split[2], "signature")?;
+
+        Ok(Self {
+            raw: raw.to_string(),
+            header,
+            payload,
+            signature,
+        })
+    }
+
+    /// The compact serialization the token was parsed from.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// `BASE64URL(Header) || '.' || BASE64URL(Payload)`, the bytes the signature covers.
+    pub fn signing_input(&self) -> &str {
+        let end = self.raw.rfind('.').unwrap_or(self.raw.len());
+        &self.raw[..end]
+    }
+}
+
+/// An id_token with an RS256 header for key `k1`, `payload` and a dummy signature.
+#[cfg(test)]
+pub(crate) fn test_token(payload: &str) -> IdToken {
+    let base64url_engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    IdToken::parse(&format!(
+        "{}.{}.{}",
+        base64url_engine.encode(r#"{"alg":"RS256","kid":"k1"}"#),
+        base64url_engine.encode(payload),
+        base64url_engine.encode("sig")
+    ))
+    .unwrap()
+}
@@ -0,0 +1,450 @@
+//! Import an SRS from a public powers-of-tau ceremony instead of generating one.
+//!
+//! Supports the snarkjs `.ptau` format and the BN254 challenge (uncompressed) and response
+//! (compressed) files of the perpetual powers of tau. Only the first `2^k + 1` powers of tau in
+//! G1 and the first two in G2 are read, every point is checked to be on the curve and in the
+//! prime order subgroup, and a random linear combination checks with two pairings that the G1
+//! points are successive powers of the G2 `tau`.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+};
+
+use clap::ValueEnum;
+use plonk::{
+    ark_bn254::{Bn254, Fq, Fq2, FqParameters, Fr, G1Affine, G2Affine},
+    ark_ec::{
+        models::SWModelParameters, msm::VariableBaseMSM, short_weierstrass_jacobian::GroupAffine,
+        AffineCurve, PairingEngine,
+    },
+    ark_ff::{BigInteger256, FpParameters, PrimeField, UniformRand},
+    kzg10::{PCKey, VKey},
+};
+use rand::RngCore;
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CeremonyFormat {
+    /// snarkjs `.ptau`
+    Ptau,
+    /// perpetual powers of tau `challenge`, with uncompressed points
+    PpotChallenge,
+    /// perpetual powers of tau `response`, with compressed points
+    PpotResponse,
+}
+
+/// Read the SRS for circuits of up to `2^k` gates from a ceremony transcript.
+pub fn import_params<R: Read + Seek, G: RngCore>(
+    format: CeremonyFormat,
+    reader: &mut R,
+    k: u32,
+    rng: &mut G,
+) -> Result<PCKey<Bn254>> {
+    let max_degree = 1usize << k;
+    let (powers, g2) = match format {
+        CeremonyFormat::Ptau => read_ptau(reader, max_degree + 1)?,
+        CeremonyFormat::PpotChallenge => read_ppot(reader, max_degree + 1, false)?,
+        CeremonyFormat::PpotResponse => read_ppot(reader, max_degree + 1, true)?,
+    };
+    check_powers(&powers, &g2, rng)?;
+
+    Ok(PCKey {
+        vk: VKey {
+            g: powers[0],
+            h: g2[0],
+            beta_h: g2[1],
+            max_degree,
+        },
+        powers,
+        max_degree,
+    })
+}
+
+fn read_ptau<R: Read + Seek>(
+    reader: &mut R,
+    num_powers: usize,
+) -> Result<(Vec<G1Affine>, [G2Affine; 2])> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"ptau" {
+        return Err(invalid("ptau: not a .ptau file"));
+    }
+    let _version = read_u32(reader)?;
+    let num_sections = read_u32(reader)?;
+    let mut sections = HashMap::new();
+    for _ in 0..num_sections {
+        let kind = read_u32(reader)?;
+        let size = read_u64(reader)?;
+        sections.insert(kind, (reader.stream_position()?, size));
+        reader.seek(SeekFrom::Current(size as i64))?;
+    }
+    let section = |reader: &mut R, kind: u32, name: &str| -> Result<u64> {
+        let (offset, size) = *sections
+            .get(&kind)
+            .ok_or_else(|| invalid(format!("ptau: missing {} section", name)))?;
+        reader.seek(SeekFrom::Start(offset))?;
+        Ok(size)
+    };
+
+    section(reader, 1, "header")?;
+    let n8 = read_u32(reader)?;
+    let mut q = [0u8; 32];
+    if n8 == 32 {
+        reader.read_exact(&mut q)?;
+    }
+    if n8 != 32 || bigint_le(&q) != FqParameters::MODULUS {
+        return Err(invalid("ptau: not a BN254 ceremony"));
+    }
+    let power = read_u32(reader)?;
+    let num_g1 = (2usize << power) - 1;
+    if num_powers > num_g1 {
+        return Err(invalid(format!(
+            "ptau: has {} powers of tau, {} needed",
+            num_g1, num_powers
+        )));
+    }
+
+    if section(reader, 2, "tauG1")? != num_g1 as u64 * 64 {
+        return Err(invalid("ptau: tauG1 section size"));
+    }
+    let powers = (0..num_powers)
+        .map(|i| read_g1(reader, Encoding::Montgomery).map_err(|e| point_error("tauG1", i, e)))
+        .collect::<Result<Vec<_>>>()?;
+    if section(reader, 3, "tauG2")? != (1u64 << power) * 128 {
+        return Err(invalid("ptau: tauG2 section size"));
+    }
+    let g2 = [0, 1]
+        .map(|i| read_g2(reader, Encoding::Montgomery).map_err(|e| point_error("tauG2", i, e)));
+    let [h, beta_h] = g2;
+    Ok((powers, [h?, beta_h?]))
+}
+
+fn read_ppot<R: Read + Seek>(
+    reader: &mut R,
+    num_powers: usize,
+    compressed: bool,
+) -> Result<(Vec<G1Affine>, [G2Affine; 2])> {
+    // A blake2b hash of the previous file, then 2N - 1 tau G1, N tau G2, N alpha tau G1,
+    // N beta tau G1 and beta G2, and in a response the contribution's public key.
+    // Past the hash, the file is `per_power * N + tail` bytes.
+    let (g1_size, per_power, tail) = if compressed {
+        (32u64, 32 * 2 + 64 + 32 + 32, 64 + 768 - 32)
+    } else {
+        (64u64, 64 * 2 + 128 + 64 + 64, 128 - 64)
+    };
+    let len = reader.seek(SeekFrom::End(0))?;
+    let n = len
+        .checked_sub(64 + tail)
+        .filter(|rest| rest % per_power == 0)
+        .map(|rest| rest / per_power)
+        .filter(|n| n.is_power_of_two())
+        .ok_or_else(|| {
+            invalid(format!(
+                "ppot: {} bytes is not the size of a {} file",
+                len,
+                if compressed { "response" } else { "challenge" }
+            ))
+        })?;
+    let num_g1 = 2 * n as usize - 1;
+    if num_powers > num_g1 {
+        return Err(invalid(format!(
+            "ppot: has {} powers of tau, {} needed",
+            num_g1, num_powers
+        )));
+    }
+    let encoding = if compressed {
+        Encoding::Compressed
+    } else {
+        Encoding::Uncompressed
+    };
+
+    reader.seek(SeekFrom::Start(64))?;
+    let powers = (0..num_powers)
+        .map(|i| read_g1(reader, encoding).map_err(|e| point_error("tauG1", i, e)))
+        .collect::<Result<Vec<_>>>()?;
+    reader.seek(SeekFrom::Start(64 + num_g1 as u64 * g1_size))?;
+    let g2 = [0, 1].map(|i| read_g2(reader, encoding).map_err(|e| point_error("tauG2", i, e)));
+    let [h, beta_h] = g2;
+    Ok((powers, [h?, beta_h?]))
+}
+
+/// Check that the powers start at the generators and that `powers[i + 1] = tau * powers[i]`
+/// for the `tau` of `g2[1] = tau * g2[0]`.
+pub(crate) fn check_powers<G: RngCore>(
+    powers: &[G1Affine],
+    g2: &[G2Affine; 2],
+    rng: &mut G,
+) -> Result<()> {
+    if powers[0] != G1Affine::prime_subgroup_generator()
+        || g2[0] != G2Affine::prime_subgroup_generator()
+    {
+        return Err(invalid("the first powers of tau are not the generators"));
+    }
+    let scalars: Vec<_> = (1..powers.len())
+        .map(|_| Fr::rand(rng).into_repr())
+        .collect();
+    let next = VariableBaseMSM::multi_scalar_mul(&powers[1..], &scalars);
+    let prev = VariableBaseMSM::multi_scalar_mul(&powers[..powers.len() - 1], &scalars);
+    if Bn254::pairing(next, g2[0]) != Bn254::pairing(prev, g2[1]) {
+        return Err(invalid(
+            "the G1 points are not successive powers of the G2 tau",
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+    /// snarkjs: little endian Montgomery form, `x` then `y`, `c0` before `c1`.
+    Montgomery,
+    /// bellman: big endian, `x` then `y`, `c1` before `c0`, flags in the top two bits.
+    Uncompressed,
+    /// bellman: the big endian `x` with the sign of `y` in the flags.
+    Compressed,
+}
+
+// BN254 coordinates leave only the top two bits free. In an uncompressed point the top one must
+// be unset, in a compressed one it is set for the greater `y`.
+const FLAG_GREATEST: u8 = 0x80;
+const FLAG_INFINITY: u8 = 0x40;
+
+fn read_g1<R: Read>(reader: &mut R, encoding: Encoding) -> Result<G1Affine> {
+    let point = match encoding {
+        Encoding::Montgomery => {
+            let mut bytes = [0u8; 64];
+            reader.read_exact(&mut bytes)?;
+            G1Affine::new(fq_le(&bytes[..32])?, fq_le(&bytes[32..])?, false)
+        }
+        Encoding::Uncompressed => {
+            let mut bytes = [0u8; 64];
+            reader.read_exact(&mut bytes)?;
+            bellman_flags(&mut bytes, false)?;
+            G1Affine::new(fq_be(&bytes[..32])?, fq_be(&bytes[32..])?, false)
+        }
+        Encoding::Compressed => {
+            let mut bytes = [0u8; 32];
+            reader.read_exact(&mut bytes)?;
+            let greatest = bellman_flags(&mut bytes, true)?;
+            G1Affine::get_point_from_x(fq_be(&bytes)?, greatest)
+                .ok_or_else(|| invalid("not on the curve"))?
+        }
+    };
+    check_point(point)
+}
+
+fn read_g2<R: Read>(reader: &mut R, encoding: Encoding) -> Result<G2Affine> {
+    let point = match encoding {
+        Encoding::Montgomery => {
+            let mut bytes = [0u8; 128];
+            reader.read_exact(&mut bytes)?;
+            let fq = |i: usize| fq_le(&bytes[i * 32..(i + 1) * 32]);
+            G2Affine::new(Fq2::new(fq(0)?, fq(1)?), Fq2::new(fq(2)?, fq(3)?), false)
+        }
+        Encoding::Uncompressed => {
+            let mut bytes = [0u8; 128];
+            reader.read_exact(&mut bytes)?;
+            bellman_flags(&mut bytes, false)?;
+            let fq = |i: usize| fq_be(&bytes[i * 32..(i + 1) * 32]);
+            G2Affine::new(Fq2::new(fq(1)?, fq(0)?), Fq2::new(fq(3)?, fq(2)?), false)
+        }
+        Encoding::Compressed => {
+            let mut bytes = [0u8; 64];
+            reader.read_exact(&mut bytes)?;
+            let greatest = bellman_flags(&mut bytes, true)?;
+            let x = Fq2::new(fq_be(&bytes[32..])?, fq_be(&bytes[..32])?);
+            G2Affine::get_point_from_x(x, greatest).ok_or_else(|| invalid("not on the curve"))?
+        }
+    };
+    check_point(point)
+}
+
+fn check_point<P: SWModelParameters>(point: GroupAffine<P>) -> Result<GroupAffine<P>> {
+    if !point.is_on_curve() {
+        return Err(invalid("not on the curve"));
+    }
+    if !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(invalid("not in the prime order subgroup"));
+    }
+    Ok(point)
+}
+
+/// Check and clear the flags of a bellman point, returning whether `y` is the greater root.
+fn bellman_flags(bytes: &mut [u8], compressed: bool) -> Result<bool> {
+    let flags = bytes[0];
+    if !compressed && flags & FLAG_GREATEST != 0 {
+        return Err(invalid("unexpected compression flag"));
+    }
+    if flags & FLAG_INFINITY != 0 {
+        return Err(invalid("the point at infinity"));
+    }
+    bytes[0] &= 0x3f;
+    Ok(flags & FLAG_GREATEST != 0)
+}
+
+fn fq_le(bytes: &[u8]) -> Result<Fq> {
+    let repr = bigint_le(bytes);
+    if repr >= FqParameters::MODULUS {
+        return Err(invalid("coordinate out of range"));
+    }
+    Ok(Fq::new(repr))
+}
+
+fn fq_be(bytes: &[u8]) -> Result<Fq> {
+    let mut le = bytes.to_vec();
+    le.reverse();
+    Fq::from_repr(bigint_le(&le)).ok_or_else(|| invalid("coordinate out of range"))
+}
+
+fn bigint_le(bytes: &[u8]) -> BigInteger256 {
+    let mut limbs = [0u64; 4];
+    for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks(8)) {
+        *limb = u64::from_le_bytes(chunk.try_into().unwrap());
+    }
+    BigInteger256::new(limbs)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn invalid(msg: impl Into<String>) -> Error {
+    Error::InvalidArtifact(msg.into())
+}
+
+fn point_error(section: &str, i: usize, e: Error) -> Error {
+    match e {
+        Error::InvalidArtifact(msg) => invalid(format!("{}[{}]: {}", section, i, msg)),
+        e => e,
+    }
+}
+
+#[test]
+fn test_import_params() {
+    use std::io::Cursor;
+
+    use plonk::{
+        ark_ec::ProjectiveCurve,
+        ark_ff::{BigInteger, Field},
+    };
+
+    let tau = Fr::from(7u64);
+    let powers = |len: usize| -> Vec<Fr> { (0..len).map(|i| tau.pow([i as u64])).collect() };
+    let g1 = |s: Fr| G1Affine::prime_subgroup_generator().mul(s).into_affine();
+    let g2 = |s: Fr| G2Affine::prime_subgroup_generator().mul(s).into_affine();
+    let mont = |fq: Fq| fq.0.to_bytes_le();
+    let be = |fq: Fq| fq.into_repr().to_bytes_be();
+
+    // A ceremony of power 3 for k = 2.
+    let mut tau_g1: Vec<u8> = powers(15)
+        .into_iter()
+        .flat_map(|s| [mont(g1(s).x), mont(g1(s).y)].concat())
+        .collect();
+    let tau_g2: Vec<u8> = powers(8)
+        .into_iter()
+        .flat_map(|s| {
+            let p = g2(s);
+            [mont(p.x.c0), mont(p.x.c1), mont(p.y.c0), mont(p.y.c1)].concat()
+        })
+        .collect();
+    let ptau = |tau_g1: &[u8]| {
+        let header = [
+            32u32.to_le_bytes().to_vec(),
+            FqParameters::MODULUS.to_bytes_le(),
+            3u32.to_le_bytes().to_vec(),
+            3u32.to_le_bytes().to_vec(),
+        ]
+        .concat();
+        let mut file = [&b"ptau"[..], &1u32.to_le_bytes(), &3u32.to_le_bytes()].concat();
+        for (kind, section) in [(1u32, &header[..]), (2, tau_g1), (3, &tau_g2)] {
+            file.extend_from_slice(&kind.to_le_bytes());
+            file.extend_from_slice(&(section.len() as u64).to_le_bytes());
+            file.extend_from_slice(section);
+        }
+        Cursor::new(file)
+    };
+
+    let mut rng = rand::thread_rng();
+    let pckey = import_params(CeremonyFormat::Ptau, &mut ptau(&tau_g1), 2, &mut rng).unwrap();
+    assert_eq!(pckey.powers.len(), 5);
+    assert_eq!(pckey.powers[4], g1(tau.pow([4])));
+    assert_eq!(pckey.vk.beta_h, g2(tau));
+    assert!(import_params(CeremonyFormat::Ptau, &mut ptau(&tau_g1), 4, &mut rng).is_err());
+
+    // Swap tau^1 and tau^2.
+    let (first, rest) = tau_g1.split_at_mut(128);
+    first[64..].swap_with_slice(&mut rest[..64]);
+    assert!(matches!(
+        import_params(CeremonyFormat::Ptau, &mut ptau(&tau_g1), 2, &mut rng),
+        Err(Error::InvalidArtifact(msg)) if msg.contains("successive powers")
+    ));
+
+    // A perpetual powers of tau response with N = 4.
+    let flags = |greatest: bool| if greatest { FLAG_GREATEST } else { 0 };
+    let mut response = vec![0u8; 64];
+    for s in powers(7) {
+        let p = g1(s);
+        let mut x = be(p.x);
+        x[0] |= flags(p.y > -p.y);
+        response.extend_from_slice(&x);
+    }
+    for s in powers(4) {
+        let p = g2(s);
+        let mut x = [be(p.x.c1), be(p.x.c0)].concat();
+        x[0] |= flags(p.y > -p.y);
+        response.extend_from_slice(&x);
+    }
+    response.resize(response.len() + 4 * 32 * 2 + 64 + 768, 0);
+    let pckey = import_params(
+        CeremonyFormat::PpotResponse,
+        &mut Cursor::new(response),
+        1,
+        &mut rng,
+    )
+    .unwrap();
+    assert_eq!(pckey.powers[2], g1(tau.pow([2])));
+    assert_eq!(pckey.vk.beta_h, g2(tau));
+
+    // The challenge of the same ceremony, with uncompressed points.
+    let mut challenge = vec![0u8; 64];
+    for s in powers(7) {
+        let p = g1(s);
+        challenge.extend_from_slice(&[be(p.x), be(p.y)].concat());
+    }
+    for s in powers(4) {
+        let p = g2(s);
+        challenge.extend_from_slice(&[be(p.x.c1), be(p.x.c0), be(p.y.c1), be(p.y.c0)].concat());
+    }
+    challenge.resize(challenge.len() + 4 * 64 * 2 + 128, 0);
+    let pckey = import_params(
+        CeremonyFormat::PpotChallenge,
+        &mut Cursor::new(challenge.clone()),
+        2,
+        &mut rng,
+    )
+    .unwrap();
+    assert_eq!(pckey.powers.len(), 5);
+    assert_eq!(pckey.powers[4], g1(tau.pow([4])));
+    assert_eq!(pckey.vk.beta_h, g2(tau));
+
+    // tau^1 with the compression flag of a response.
+    challenge[64 + 64] |= FLAG_GREATEST;
+    assert!(matches!(
+        import_params(
+            CeremonyFormat::PpotChallenge,
+            &mut Cursor::new(challenge),
+            2,
+            &mut rng,
+        ),
+        Err(Error::InvalidArtifact(msg)) if msg == "tauG1[1]: unexpected compression flag"
+    ));
+}
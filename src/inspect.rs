@@ -0,0 +1,396 @@
+//! Report what is inside a set of params, proving key and verifier comms, and check it against
+//! the `zkConfigs.json` the contracts are configured with.
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::Read,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use prover::{
+    parameters::{load_prover_key, load_verifier_comms},
+    utils::to_0x_hex,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    artifacts::OpenIdProver,
+    error::{Error, Result},
+    header::{circuit_id, hash_field, load_artifact, ArtifactHeader, ArtifactKind, HEADER_LEN},
+    VerifyingArtifacts, ZkConfigs,
+};
+
+/// What a set of artifacts holds. Each value is unknown when a file it comes from is missing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArtifactsReport {
+    /// The largest degree the SRS can commit to.
+    pub srs_degree: Option<usize>,
+    pub srs_hash: Option<String>,
+    pub domain_size: Option<usize>,
+    /// Public inputs of the circuit, as recorded in the proving key.
+    pub num_inputs: Option<u64>,
+    /// `sha256` of the `vkdata` the contracts are configured with.
+    pub vk_hash: Option<String>,
+    pub circuit_id: Option<String>,
+    pub files: Vec<FileReport>,
+    /// Every header field that disagrees with the files or with another header.
+    pub mismatches: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileReport {
+    pub kind: String,
+    pub path: String,
+    pub size: u64,
+    /// Unix seconds, unknown on filesystems that do not record them.
+    pub created: Option<u64>,
+    pub modified: Option<u64>,
+    /// Unknown for a file written before the artifact header.
+    pub format_version: Option<u16>,
+}
+
+impl ArtifactsReport {
+    /// Load each of the given files on its own and report on them, listing the header fields
+    /// that disagree instead of refusing the set.
+    pub fn inspect(
+        params_path: Option<&str>,
+        pk_path: Option<&str>,
+        vc_path: Option<&str>,
+    ) -> Result<Self> {
+        let mut files = vec![];
+        let mut headers = vec![];
+        let mut read = |kind, path: &str, header: Option<ArtifactHeader>| -> Result<()> {
+            files.push(FileReport::read(kind, path)?);
+            headers.extend(header.map(|header| (path.to_string(), header)));
+            Ok(())
+        };
+
+        let pckey = match params_path {
+            Some(path) => {
+                let (pckey, header) = load_artifact(
+                    path,
+                    ArtifactKind::Params,
+                    None,
+                    prover::parameters::load_params,
+                )?;
+                read(ArtifactKind::Params, path, header)?;
+                Some(pckey)
+            }
+            None => None,
+        };
+        let pk = match pk_path {
+            Some(path) => {
+                let (pk, header) =
+                    load_artifact(path, ArtifactKind::ProvingKey, None, load_prover_key)?;
+                read(ArtifactKind::ProvingKey, path, header)?;
+                Some(pk)
+            }
+            None => None,
+        };
+        let vc = match vc_path {
+            Some(path) => {
+                let (vc, header) =
+                    load_artifact(path, ArtifactKind::VerifierComms, None, load_verifier_comms)?;
+                read(ArtifactKind::VerifierComms, path, header)?;
+                Some(vc)
+            }
+            None => None,
+        };
+
+        let sha256_of_srs = pckey.as_ref().map(|pckey| pckey.sha256_of_srs());
+        let num_inputs = pk.as_ref().map(|pk| pk.input_size as u64);
+        let circuit_id = pk.as_ref().map(circuit_id).transpose()?;
+        let prover = pk.map(OpenIdProver::new);
+        let vk_hash = match (&pckey, &prover, vc, &sha256_of_srs) {
+            (Some(pckey), Some(prover), Some(vc), Some(sha256_of_srs)) => Some(
+                VerifyingArtifacts {
+                    domain: prover.domain,
+                    vc,
+                    vk: pckey.vk.clone(),
+                    sha256_of_srs: sha256_of_srs.clone(),
+                    circuit_id,
+                    proof_commitments: None,
+                }
+                .vk_hash()?,
+            ),
+            _ => None,
+        };
+        let mismatches = header_mismatches(
+            &headers,
+            sha256_of_srs.as_deref().map(hash_field),
+            circuit_id,
+            vk_hash,
+        );
+
+        Ok(Self {
+            srs_degree: pckey.as_ref().map(|pckey| pckey.max_degree),
+            srs_hash: sha256_of_srs.map(to_0x_hex),
+            domain_size: prover.as_ref().map(|prover| prover.domain_size()),
+            num_inputs,
+            vk_hash: vk_hash.map(to_0x_hex),
+            circuit_id: circuit_id.map(to_0x_hex),
+            files,
+            mismatches,
+        })
+    }
+
+    /// Every field of `zk_configs` that does not describe these artifacts, or that cannot be
+    /// checked without one of the files.
+    pub fn check_zk_configs(&self, zk_configs: &ZkConfigs) -> Vec<String> {
+        let vk_hash = hex::decode(zk_configs.vkdata.trim_start_matches("0x")).map_or_else(
+            |_| "not hex".to_string(),
+            |vkdata| to_0x_hex(Sha256::digest(vkdata)),
+        );
+        [
+            (
+                "srs_hash",
+                zk_configs.srs_hash.clone(),
+                self.srs_hash.clone(),
+                "the params",
+            ),
+            (
+                "domain_size",
+                zk_configs.domain_size.to_string(),
+                self.domain_size.map(|size| size.to_string()),
+                "the proving key",
+            ),
+            (
+                "num_inputs",
+                zk_configs.num_inputs.to_string(),
+                self.num_inputs.map(|num| num.to_string()),
+                "the proving key",
+            ),
+            (
+                "vkdata hash",
+                vk_hash,
+                self.vk_hash.clone(),
+                "the params, proving key and verifier comms",
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, found, expected, needs)| match expected {
+            Some(expected) if found.eq_ignore_ascii_case(&expected) => None,
+            Some(expected) => Some(format!("{} is {}, expected {}", name, found, expected)),
+            None => Some(format!("{} cannot be checked without {}", name, needs)),
+        })
+        .collect()
+    }
+}
+
+/// A hash every artifact header records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HeaderField {
+    SrsHash,
+    CircuitId,
+    VkHash,
+}
+
+impl HeaderField {
+    fn get(self, header: &ArtifactHeader) -> [u8; 32] {
+        match self {
+            HeaderField::SrsHash => header.srs_hash,
+            HeaderField::CircuitId => header.circuit_id,
+            HeaderField::VkHash => header.vk_hash,
+        }
+    }
+
+    /// Params belong to no circuit, their header only records the SRS hash.
+    fn recorded_by(self, kind: ArtifactKind) -> bool {
+        self == HeaderField::SrsHash || kind != ArtifactKind::Params
+    }
+}
+
+impl fmt::Display for HeaderField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HeaderField::SrsHash => "SRS hash",
+            HeaderField::CircuitId => "circuit id",
+            HeaderField::VkHash => "vk hash",
+        })
+    }
+}
+
+/// The fields of `headers` that differ from the values computed from the files or, when a value
+/// is unknown, from the first header that records it.
+fn header_mismatches(
+    headers: &[(String, ArtifactHeader)],
+    srs_hash: Option<[u8; 32]>,
+    circuit_id: Option<[u8; 32]>,
+    vk_hash: Option<[u8; 32]>,
+) -> Vec<String> {
+    let mut mismatches = vec![];
+    for (field, computed) in [
+        (HeaderField::SrsHash, srs_hash),
+        (HeaderField::CircuitId, circuit_id),
+        (HeaderField::VkHash, vk_hash),
+    ] {
+        let recording: Vec<_> = headers
+            .iter()
+            .filter(|(_, header)| field.recorded_by(header.kind))
+            .collect();
+        let expected = match (computed, recording.first()) {
+            (Some(computed), _) => (computed, "the files".to_string()),
+            (None, Some((path, header))) => {
+                (field.get(header), format!("{} {}", header.kind, path))
+            }
+            (None, None) => continue,
+        };
+        for (path, header) in recording {
+            if field.get(header) != expected.0 {
+                mismatches.push(format!(
+                    "{} {} has {} {}, expected {} from {}",
+                    header.kind,
+                    path,
+                    field,
+                    to_0x_hex(field.get(header)),
+                    to_0x_hex(expected.0),
+                    expected.1
+                ));
+            }
+        }
+    }
+    mismatches
+}
+
+impl FileReport {
+    fn read(kind: ArtifactKind, path: &str) -> Result<Self> {
+        let io = |e| Error::io_at(path, e);
+        let metadata = fs::metadata(path).map_err(io)?;
+        let mut header = vec![];
+        File::open(path)
+            .and_then(|file| file.take(HEADER_LEN as u64).read_to_end(&mut header))
+            .map_err(io)?;
+        let unix_secs = |time: std::io::Result<SystemTime>| {
+            time.ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs())
+        };
+        Ok(Self {
+            kind: kind.to_string(),
+            path: path.to_string(),
+            size: metadata.len(),
+            created: unix_secs(metadata.created()),
+            modified: unix_secs(metadata.modified()),
+            format_version: ArtifactHeader::split(&header, kind, path)?
+                .map(|(header, _)| header.version),
+        })
+    }
+}
+
+impl fmt::Display for ArtifactsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let or_unknown = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_string());
+        writeln!(
+            f,
+            "srs degree:     {}",
+            or_unknown(self.srs_degree.map(|degree| degree.to_string()))
+        )?;
+        writeln!(f, "sha256_of_srs:  {}", or_unknown(self.srs_hash.clone()))?;
+        writeln!(
+            f,
+            "domain size:    {}",
+            or_unknown(self.domain_size.map(|size| format!(
+                "{} (2^{})",
+                size,
+                size.trailing_zeros()
+            )))
+        )?;
+        writeln!(
+            f,
+            "public inputs:  {}",
+            or_unknown(self.num_inputs.map(|num| num.to_string()))
+        )?;
+        writeln!(f, "vk hash:        {}", or_unknown(self.vk_hash.clone()))?;
+        writeln!(f, "circuit id:     {}", or_unknown(self.circuit_id.clone()))?;
+        for file in &self.files {
+            writeln!(f, "{} {}:", file.kind, file.path)?;
+            writeln!(f, "  size:           {} bytes", file.size)?;
+            writeln!(
+                f,
+                "  created:        {}",
+                or_unknown(file.created.map(|t| t.to_string()))
+            )?;
+            writeln!(
+                f,
+                "  modified:       {}",
+                or_unknown(file.modified.map(|t| t.to_string()))
+            )?;
+            match file.format_version {
+                Some(version) => writeln!(f, "  format version: {}", version)?,
+                None => writeln!(f, "  format version: none, written before the header")?,
+            }
+        }
+        for mismatch in &self.mismatches {
+            writeln!(f, "mismatch: {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_check_zk_configs() {
+    let vkdata = [0u8, 0, 0, 1, 7];
+    let mut report = ArtifactsReport {
+        srs_degree: Some(1 << 20),
+        srs_hash: Some(to_0x_hex([1; 32])),
+        domain_size: Some(1 << 20),
+        num_inputs: Some(1),
+        vk_hash: Some(to_0x_hex(Sha256::digest(vkdata))),
+        circuit_id: Some(to_0x_hex([2; 32])),
+        files: vec![],
+        mismatches: vec![],
+    };
+    let mut zk_configs = ZkConfigs {
+        srs_hash: to_0x_hex([1; 32]).to_uppercase().replace("0X", "0x"),
+        num_inputs: 1,
+        domain_size: 1 << 20,
+        vkdata: to_0x_hex(vkdata),
+    };
+    assert!(report.check_zk_configs(&zk_configs).is_empty());
+
+    zk_configs.domain_size = 1 << 21;
+    zk_configs.vkdata = "0x00".to_string();
+    let mismatches = report.check_zk_configs(&zk_configs);
+    assert_eq!(mismatches.len(), 2);
+    assert_eq!(mismatches[0], "domain_size is 2097152, expected 1048576");
+    assert!(mismatches[1].starts_with("vkdata hash is 0x6e34"));
+
+    report.srs_hash = None;
+    let mismatches = report.check_zk_configs(&zk_configs);
+    assert_eq!(
+        mismatches[0],
+        "srs_hash cannot be checked without the params"
+    );
+}
+
+#[test]
+fn test_header_mismatches() {
+    let header = |kind, circuit_id| ArtifactHeader {
+        version: 2,
+        kind,
+        circuit_id: [circuit_id; 32],
+        srs_hash: [1; 32],
+        vk_hash: [3; 32],
+    };
+    let headers = vec![
+        ("params.bin".to_string(), header(ArtifactKind::Params, 0)),
+        ("app.pk".to_string(), header(ArtifactKind::ProvingKey, 2)),
+        ("app.vc".to_string(), header(ArtifactKind::VerifierComms, 4)),
+    ];
+    assert!(header_mismatches(&headers[..2], Some([1; 32]), Some([2; 32]), None).is_empty());
+
+    let mismatches = header_mismatches(&headers, None, None, None);
+    assert_eq!(
+        mismatches,
+        [format!(
+            "verifier comms app.vc has circuit id {}, expected {} from proving key app.pk",
+            to_0x_hex([4; 32]),
+            to_0x_hex([2; 32])
+        )]
+    );
+    let mismatches = header_mismatches(&headers, Some([5; 32]), Some([4; 32]), None);
+    assert_eq!(mismatches.len(), 4);
+    assert!(mismatches[0].starts_with("params params.bin has SRS hash 0x0101"));
+}
@@ -0,0 +1,401 @@
+//! A minimal JSON tokenizer that reports where keys and values sit in the raw bytes.
+//!
+//! The contracts slice claims straight out of the decoded header and payload, so the offsets
+//! must refer to the exact bytes the IdP signed rather than to a re-serialized document.
+
+use crate::error::{Error, Result};
+
+/// How deeply objects and arrays may nest, so that hostile input cannot exhaust the stack.
+pub const MAX_DEPTH: usize = 64;
+
+/// The byte range `[left, right)` of a token in the raw JSON.
+///
+/// For strings the range covers the contents between the quotes, escapes left as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub left: usize,
+    pub right: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    Number,
+    Bool,
+    Null,
+    Object,
+    Array,
+}
+
+/// A member of the top-level JSON object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Member {
+    pub key: Span,
+    pub value: Span,
+    pub kind: ValueKind,
+}
+
+/// A tokenized top-level object, to look up several members without tokenizing it again.
+#[derive(Debug, Clone)]
+pub struct Object<'a> {
+    json: &'a [u8],
+    members: Vec<Member>,
+}
+
+impl<'a> Object<'a> {
+    pub fn parse(json: &'a [u8]) -> Result<Self> {
+        Ok(Self {
+            json,
+            members: object_members(json)?,
+        })
+    }
+
+    pub fn json(&self) -> &'a [u8] {
+        self.json
+    }
+
+    /// Find the member named `name`, rejecting objects that repeat it.
+    pub fn find(&self, name: &str) -> Result<Option<Member>> {
+        let mut found = None;
+        for member in &self.members {
+            if string_eq(self.json, member.key, name)? {
+                if found.is_some() {
+                    return Err(Error::MalformedJwt(format!("duplicate key {}", name)));
+                }
+                found = Some(*member);
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// Tokenize `json`, which must be a single object, into its top-level members.
+pub fn object_members(json: &[u8]) -> Result<Vec<Member>> {
+    let mut tokenizer = Tokenizer::new(json, 0);
+    tokenizer.skip_whitespace();
+    let members = tokenizer.object()?;
+    tokenizer.skip_whitespace();
+    if tokenizer.pos != json.len() {
+        return Err(Error::MalformedJwt(format!(
+            "trailing data at byte {}",
+            tokenizer.pos
+        )));
+    }
+    Ok(members)
+}
+
+/// Find the member named `name`, rejecting objects that repeat it.
+pub fn find_member(json: &[u8], name: &str) -> Result<Option<Member>> {
+    Object::parse(json)?.find(name)
+}
+
+/// Tokenize the array at `span` into the spans of its elements.
+pub fn array_elements(json: &[u8], span: Span) -> Result<Vec<(Span, ValueKind)>> {
+    Tokenizer::new(&json[..span.right], span.left).array()
+}
+
+/// Compare the string at `span` with `value` after unescaping it.
+pub fn string_eq(json: &[u8], span: Span, value: &str) -> Result<bool> {
+    let raw = &json[span.left..span.right];
+    if !raw.contains(&b'\\') {
+        return Ok(raw == value.as_bytes());
+    }
+    let unescaped: String = serde_json::from_slice(&json[span.left - 1..span.right + 1])
+        .map_err(|e| Error::MalformedJwt(e.to_string()))?;
+    Ok(unescaped == value)
+}
+
+struct Tokenizer<'a> {
+    json: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(json: &'a [u8], pos: usize) -> Self {
+        Self {
+            json,
+            pos,
+            depth: 0,
+        }
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(Error::MalformedJwt(format!(
+                "nested deeper than {} at byte {}",
+                MAX_DEPTH, self.pos
+            )));
+        }
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.json.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        match self.peek() {
+            Some(b) if b == byte => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn unexpected(&self) -> Error {
+        Error::MalformedJwt(match self.peek() {
+            Some(b) => format!("unexpected {:?} at byte {}", b as char, self.pos),
+            None => "unexpected end of json".to_string(),
+        })
+    }
+
+    fn object(&mut self) -> Result<Vec<Member>> {
+        self.expect(b'{')?;
+        self.enter()?;
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(members);
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            self.skip_whitespace();
+            let (value, kind) = self.value()?;
+            members.push(Member { key, value, kind });
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    self.depth -= 1;
+                    return Ok(members);
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Vec<(Span, ValueKind)>> {
+        self.expect(b'[')?;
+        self.enter()?;
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(elements);
+        }
+        loop {
+            self.skip_whitespace();
+            elements.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    self.depth -= 1;
+                    return Ok(elements);
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<(Span, ValueKind)> {
+        let left = self.pos;
+        let kind = match self.peek() {
+            Some(b'"') => return Ok((self.string()?, ValueKind::String)),
+            Some(b'{') => {
+                self.object()?;
+                ValueKind::Object
+            }
+            Some(b'[') => {
+                self.array()?;
+                ValueKind::Array
+            }
+            Some(b't') => {
+                self.literal(b"true")?;
+                ValueKind::Bool
+            }
+            Some(b'f') => {
+                self.literal(b"false")?;
+                ValueKind::Bool
+            }
+            Some(b'n') => {
+                self.literal(b"null")?;
+                ValueKind::Null
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                self.number()?;
+                ValueKind::Number
+            }
+            _ => return Err(self.unexpected()),
+        };
+        Ok((
+            Span {
+                left,
+                right: self.pos,
+            },
+            kind,
+        ))
+    }
+
+    fn string(&mut self) -> Result<Span> {
+        self.expect(b'"')?;
+        let left = self.pos;
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    let span = Span {
+                        left,
+                        right: self.pos,
+                    };
+                    self.pos += 1;
+                    return Ok(span);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => {
+                            self.pos += 1
+                        }
+                        Some(b'u') => {
+                            self.pos += 1;
+                            for _ in 0..4 {
+                                match self.peek() {
+                                    Some(b) if b.is_ascii_hexdigit() => self.pos += 1,
+                                    _ => return Err(self.unexpected()),
+                                }
+                            }
+                        }
+                        _ => return Err(self.unexpected()),
+                    }
+                }
+                Some(0x00..=0x1f) | None => return Err(self.unexpected()),
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<()> {
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => self.digits(),
+            _ => return Err(self.unexpected()),
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            self.required_digits()?;
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            self.required_digits()?;
+        }
+        Ok(())
+    }
+
+    fn digits(&mut self) {
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn required_digits(&mut self) -> Result<()> {
+        let start = self.pos;
+        self.digits();
+        if self.pos == start {
+            return Err(self.unexpected());
+        }
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &[u8]) -> Result<()> {
+        if !self.json[self.pos..].starts_with(literal) {
+            return Err(self.unexpected());
+        }
+        self.pos += literal.len();
+        Ok(())
+    }
+}
+
+#[test]
+fn test_find_member_spans() {
+    let json = br#"{"iss": "https://a.com", "name":"x\"iss\":\"evil", "iat" :1694678493 , "x":{"sub":"no"},"sub":"123"}"#;
+
+    let iss = find_member(json, "iss").unwrap().unwrap();
+    assert_eq!(iss.kind, ValueKind::String);
+    assert_eq!(&json[iss.value.left..iss.value.right], b"https://a.com");
+
+    let iat = find_member(json, "iat").unwrap().unwrap();
+    assert_eq!(iat.kind, ValueKind::Number);
+    assert_eq!(&json[iat.value.left..iat.value.right], b"1694678493");
+
+    let sub = find_member(json, "sub").unwrap().unwrap();
+    assert_eq!(&json[sub.value.left..sub.value.right], b"123");
+
+    assert!(find_member(json, "exp").unwrap().is_none());
+}
+
+#[test]
+fn test_escaped_values_and_keys() {
+    let json = br#"{"nonce":"a\"b\\","aud":"c"}"#;
+    let nonce = find_member(json, "nonce").unwrap().unwrap();
+    assert_eq!(&json[nonce.value.left..nonce.value.right], br#"a\"b\\"#);
+    let aud = find_member(json, "aud").unwrap().unwrap();
+    assert_eq!(&json[aud.value.left..aud.value.right], b"c");
+}
+
+#[test]
+fn test_array_elements() {
+    let json = br#"{"aud": [ "a", "b\u0063" ,"d"]}"#;
+    let aud = find_member(json, "aud").unwrap().unwrap();
+    assert_eq!(aud.kind, ValueKind::Array);
+    let elements = array_elements(json, aud.value).unwrap();
+    assert_eq!(elements.len(), 3);
+    assert_eq!(&json[elements[0].0.left..elements[0].0.right], b"a");
+    assert!(string_eq(json, elements[1].0, "bc").unwrap());
+}
+
+#[test]
+fn test_rejects_invalid_json() {
+    assert!(object_members(br#"{"iss":"a""#).is_err());
+    assert!(object_members(br#"{"iss":"a",}"#).is_err());
+    assert!(object_members(br#"{"iss":01}"#).is_err());
+    assert!(object_members(br#"{"iss":"a"} x"#).is_err());
+    assert!(find_member(br#"{"iss":"a","iss":"b"}"#, "iss").is_err());
+}
+
+#[test]
+fn test_rejects_deep_nesting() {
+    let nested = |depth: usize| format!(r#"{{"a":{}1{}}}"#, "[".repeat(depth), "]".repeat(depth));
+    assert!(object_members(nested(MAX_DEPTH - 1).as_bytes()).is_ok());
+    assert!(matches!(
+        object_members(nested(MAX_DEPTH).as_bytes()),
+        Err(Error::MalformedJwt(_))
+    ));
+    let hostile = format!(r#"{{"a":{}"#, "[".repeat(1_000_000));
+    assert!(matches!(
+        object_members(hostile.as_bytes()),
+        Err(Error::MalformedJwt(_))
+    ));
+}
@@ -0,0 +1,147 @@
+use base64::Engine;
+use ethers::utils::keccak256;
+use prover::utils::to_0x_hex;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// The public exponent 65537, the only one the contracts verify signatures with.
+pub const RSA_EXPONENT: [u8; 3] = [1, 0, 1];
+
+/// A JSON Web Key as published by an IdP, only the members needed for RSA keys are kept.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub kid: Option<String>,
+    pub alg: Option<String>,
+    pub n: Option<String>,
+    pub e: Option<String>,
+}
+
+/// A JSON Web Key Set, e.g. the document behind an IdP's `jwks_uri`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+impl Jwks {
+    /// Parse a JWKS document, a single JWK is accepted as a set of one key.
+    pub fn from_json(json: &[u8]) -> Result<Self> {
+        if let Ok(jwks) = serde_json::from_slice::<Jwks>(json) {
+            return Ok(jwks);
+        }
+        let jwk: Jwk = serde_json::from_slice(json)
+            .map_err(|e| Error::InvalidKey(format!("neither a JWKS nor a JWK: {}", e)))?;
+        Ok(Jwks { keys: vec![jwk] })
+    }
+
+    pub fn find(&self, kid: &str) -> Result<&Jwk> {
+        self.keys
+            .iter()
+            .find(|jwk| jwk.kid.as_deref() == Some(kid))
+            .ok_or_else(|| Error::InvalidKey(format!("no key with kid {}", kid)))
+    }
+}
+
+impl Jwk {
+    /// The big-endian RSA modulus and public exponent.
+    pub fn rsa_components(&self) -> Result<(Vec<u8>, Vec<u8>)> {
+        if self.kty != "RSA" {
+            return Err(Error::InvalidKey(format!(
+                "expected an RSA key, found {}",
+                self.kty
+            )));
+        }
+        let base64url_engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let decode = |value: &Option<String>, name: &str| {
+            let value = value
+                .as_deref()
+                .ok_or_else(|| Error::InvalidKey(format!("missing {}", name)))?;
+            base64url_engine
+                .decode(value.trim_end_matches('='))
+                .map_err(|e| Error::InvalidKey(format!("invalid base64url {}: {}", name, e)))
+        };
+        Ok((decode(&self.n, "n")?, decode(&self.e, "e")?))
+    }
+
+    /// The key as registered in the OpenID-Auth-Contracts key registry for issuer `iss`.
+    ///
+    /// The registry only stores the modulus, so keys with another exponent than 65537 are
+    /// refused.
+    pub fn onchain_key(&self, iss: &str) -> Result<OnChainKey> {
+        let kid = self
+            .kid
+            .as_deref()
+            .ok_or_else(|| Error::InvalidKey("missing kid".to_string()))?;
+        let (modulus, exponent) = self.rsa_components()?;
+        if exponent != RSA_EXPONENT {
+            return Err(Error::InvalidKey(format!(
+                "exponent {} is not 65537",
+                to_0x_hex(exponent)
+            )));
+        }
+        Ok(OnChainKey {
+            key_id: onchain_key_id(iss, kid),
+            modulus,
+        })
+    }
+}
+
+/// `keccak256(abi.encodePacked(iss, kid))`, the id the contracts look public keys up by.
+pub fn onchain_key_id(iss: &str, kid: &str) -> [u8; 32] {
+    keccak256([iss.as_bytes(), kid.as_bytes()].concat())
+}
+
+/// An RSA public key in the form the contracts store it: the modulus under its key id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnChainKey {
+    pub key_id: [u8; 32],
+    pub modulus: Vec<u8>,
+}
+
+/// The `export-jwk` output, every byte string `0x` hex encoded.
+#[derive(Debug, Serialize)]
+pub struct ExportedJwk {
+    pub iss: String,
+    pub kid: String,
+    pub key_id: String,
+    pub modulus: String,
+}
+
+impl ExportedJwk {
+    pub fn new(iss: &str, kid: &str, key: &OnChainKey) -> Self {
+        Self {
+            iss: iss.to_string(),
+            kid: kid.to_string(),
+            key_id: to_0x_hex(key.key_id),
+            modulus: to_0x_hex(&key.modulus),
+        }
+    }
+}
+
+#[test]
+fn test_pk() {
+    let jwk = Jwk {
+        kty: "RSA".to_string(),
+        kid: Some("k1".to_string()),
+        alg: Some("RS256".to_string()),
+        n: Some("lWXY0XOj_ikSIDIvGOhfuRhQJAAj6BWsbbZ6P-PXRclzV32-QLB4GZHPPcH37Lou5pQsTQPvTETAfCLnglIRSbP8x1zA5tUakRlm5RiGF4kcWh5k60x8u0Uslx-d6EueKuY-KLHUVDuMULlHkYAScIdYnXz-Cnr6PFZj8RQezzdPVPH53Q8a_Z9b-vpGzsMS5gszITb-72OQNokojXdPVctl5WzSx-JnWbJxPiwHx_dSWgmTnyiYrZLqrqfampGdroaamtIXy0W8CAe0uCqcD1LunpfX-Q-RD1IycxnEaXSuUKhNhCcxtHWrozEyeD23Zja2WlcvHdYuTzyrvrvS9Q".to_string()),
+        e: Some("AQAB".to_string()),
+    };
+    let key = jwk.onchain_key("https://accounts.google.com").unwrap();
+    assert_eq!(key.modulus.len(), 256);
+    assert!(to_0x_hex(&key.modulus).starts_with("0x9565d8d173a3"));
+    assert_eq!(
+        key.key_id,
+        keccak256(b"https://accounts.google.comk1".as_slice())
+    );
+
+    let jwk = Jwk {
+        e: Some("Aw".to_string()),
+        ..jwk
+    };
+    assert!(matches!(
+        jwk.onchain_key("https://accounts.google.com"),
+        Err(Error::InvalidKey(_))
+    ));
+}
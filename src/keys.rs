@@ -0,0 +1,66 @@
+use plonk::EvaluationDomain;
+use prover::utils::to_0x_hex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    header::TrustedChecksums,
+    ProvingArtifacts, VerifyingArtifacts,
+};
+
+/// `keys.json`: the paths and hashes of the pk/vc pair `gen-keys` made.
+///
+/// The circuit has fixed input lengths, so there is a single key and the manifest only keeps
+/// its files together with the SRS they need.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyManifest {
+    pub pk_path: String,
+    pub vc_path: String,
+    pub verifier_path: String,
+    pub domain_size: usize,
+    /// `0x` hex `sha256` of the key's `vkdata`.
+    pub vk_hash: String,
+    /// `0x` hex `sha256` of the SRS the key was generated with.
+    pub srs_hash: String,
+}
+
+impl KeyManifest {
+    pub fn new(
+        pk_path: &str,
+        vc_path: &str,
+        verifier_path: &str,
+        verifying_artifacts: &VerifyingArtifacts,
+    ) -> Result<Self> {
+        Ok(Self {
+            pk_path: pk_path.to_string(),
+            vc_path: vc_path.to_string(),
+            verifier_path: verifier_path.to_string(),
+            domain_size: verifying_artifacts.domain.size(),
+            vk_hash: to_0x_hex(verifying_artifacts.vk_hash()?),
+            srs_hash: to_0x_hex(&verifying_artifacts.sha256_of_srs),
+        })
+    }
+
+    pub fn from_json(json: &[u8]) -> Result<Self> {
+        serde_json::from_slice(json)
+            .map_err(|e| Error::InvalidArtifact(format!("key manifest: {}", e)))
+    }
+
+    /// Load the key with `params_path`, which must hold the SRS the key was generated with.
+    pub fn load(
+        &self,
+        params_path: &str,
+        checksums: &TrustedChecksums,
+    ) -> Result<ProvingArtifacts> {
+        let artifacts =
+            ProvingArtifacts::load_trusted(params_path, &self.pk_path, &self.vc_path, checksums)?;
+        let srs_hash = to_0x_hex(&artifacts.sha256_of_srs);
+        if !srs_hash.eq_ignore_ascii_case(&self.srs_hash) {
+            return Err(Error::ArtifactMismatch(format!(
+                "{} has SRS hash {}, {} was generated with {}",
+                params_path, srs_hash, self.pk_path, self.srs_hash
+            )));
+        }
+        Ok(artifacts)
+    }
+}
@@ -0,0 +1,350 @@
+use ethers::abi::{Token, Tokenizable};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    json::{self, Object, Span, ValueKind},
+    IdToken,
+};
+
+/// Versions of the claim index section that starts the packed contract arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum FormatVersion {
+    /// Left and right indices of `iss`, `kid`, `sub` and `aud`, left indices of `nonce`, `iat`
+    /// and `exp`.
+    #[default]
+    #[value(name = "1")]
+    V1,
+    /// Left and right indices of every claim, followed by the `nbf` and `azp` spans, which are
+    /// `0, 0` when the claim is absent.
+    #[value(name = "2")]
+    V2,
+}
+
+impl FormatVersion {
+    /// Names of the [`ClaimLayout::into_tokens`] values, in the same order.
+    pub fn token_names(self) -> &'static [&'static str] {
+        match self {
+            FormatVersion::V1 => &[
+                "iss_left_index",
+                "iss_right_index",
+                "kid_left_index",
+                "kid_right_index",
+                "sub_left_index",
+                "sub_right_index",
+                "aud_left_index",
+                "aud_right_index",
+                "nonce_left_index",
+                "iat_left_index",
+                "exp_left_index",
+            ],
+            FormatVersion::V2 => &[
+                "iss_left_index",
+                "iss_right_index",
+                "kid_left_index",
+                "kid_right_index",
+                "sub_left_index",
+                "sub_right_index",
+                "aud_left_index",
+                "aud_right_index",
+                "nonce_left_index",
+                "nonce_right_index",
+                "iat_left_index",
+                "iat_right_index",
+                "exp_left_index",
+                "exp_right_index",
+                "nbf_left_index",
+                "nbf_right_index",
+                "azp_left_index",
+                "azp_right_index",
+            ],
+        }
+    }
+}
+
+impl TryFrom<u8> for FormatVersion {
+    type Error = String;
+
+    fn try_from(version: u8) -> std::result::Result<Self, Self::Error> {
+        match version {
+            1 => Ok(FormatVersion::V1),
+            2 => Ok(FormatVersion::V2),
+            version => Err(format!("unknown format version {}", version)),
+        }
+    }
+}
+
+impl From<FormatVersion> for u8 {
+    fn from(version: FormatVersion) -> u8 {
+        match version {
+            FormatVersion::V1 => 1,
+            FormatVersion::V2 => 2,
+        }
+    }
+}
+
+/// Byte offsets of the claims the contracts read out of the decoded header and payload.
+///
+/// Left indices point at the first byte of a value, right indices one past its last byte.
+/// Both the direct and the ZK arguments are encoded from it, and it serializes to JSON for
+/// inspection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClaimLayout {
+    pub header_len: u32,
+    pub payload_len: u32,
+    pub iss_left_index: u32,
+    pub iss_right_index: u32,
+    pub kid_left_index: u32,
+    pub kid_right_index: u32,
+    pub sub_left_index: u32,
+    pub sub_right_index: u32,
+    pub aud_left_index: u32,
+    pub aud_right_index: u32,
+    pub nonce_left_index: u32,
+    pub nonce_right_index: u32,
+    pub iat_left_index: u32,
+    pub iat_right_index: u32,
+    pub exp_left_index: u32,
+    pub exp_right_index: u32,
+    /// Left and right index of `nbf`, when present.
+    #[serde(default)]
+    pub nbf: Option<(u32, u32)>,
+    /// Left and right index of `azp`, when present.
+    #[serde(default)]
+    pub azp: Option<(u32, u32)>,
+}
+
+impl ClaimLayout {
+    /// Locate the claims of `id_token`.
+    ///
+    /// When `aud` is an array the indices point at the element equal to `expected_aud`, which
+    /// may only be omitted for single element arrays.
+    pub fn locate(id_token: &IdToken, expected_aud: Option<&str>) -> Result<Self> {
+        let header = Object::parse(&id_token.header)?;
+        let payload = Object::parse(&id_token.payload)?;
+
+        let (iss_left_index, iss_right_index) = claim(&payload, "iss", ValueKind::String)?;
+        let (kid_left_index, kid_right_index) = claim(&header, "kid", ValueKind::String)?;
+        let (sub_left_index, sub_right_index) = claim(&payload, "sub", ValueKind::String)?;
+        let (aud, azp) = audience(&payload, expected_aud)?;
+        let (aud_left_index, aud_right_index) = (aud.left as u32, aud.right as u32);
+        let (nonce_left_index, nonce_right_index) = claim(&payload, "nonce", ValueKind::String)?;
+        let (iat_left_index, iat_right_index) = claim(&payload, "iat", ValueKind::Number)?;
+        let (exp_left_index, exp_right_index) = claim(&payload, "exp", ValueKind::Number)?;
+        let nbf = optional_claim(&payload, "nbf", ValueKind::Number)?;
+
+        Ok(Self {
+            header_len: id_token.header.len() as u32,
+            payload_len: id_token.payload.len() as u32,
+            iss_left_index,
+            iss_right_index,
+            kid_left_index,
+            kid_right_index,
+            sub_left_index,
+            sub_right_index,
+            aud_left_index,
+            aud_right_index,
+            nonce_left_index,
+            nonce_right_index,
+            iat_left_index,
+            iat_right_index,
+            exp_left_index,
+            exp_right_index,
+            nbf,
+            azp,
+        })
+    }
+
+    /// Make sure the layout was located in `id_token` and not in another token.
+    pub fn check_lengths(&self, id_token: &IdToken) -> Result<()> {
+        if self.header_len as usize != id_token.header.len()
+            || self.payload_len as usize != id_token.payload.len()
+        {
+            return Err(Error::InvalidClaim(format!(
+                "layout of a {}/{} byte header/payload used for a {}/{} byte one",
+                self.header_len,
+                self.payload_len,
+                id_token.header.len(),
+                id_token.payload.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Length of the base64url header in the id_token, which JWTs encode without padding.
+    pub fn header_base64_len(&self) -> u32 {
+        base64_len(self.header_len)
+    }
+
+    /// Where the base64url payload starts in the id_token, past the header and its `.`.
+    pub fn payload_left_index(&self) -> u32 {
+        self.header_base64_len() + 1
+    }
+
+    /// Length of the base64url payload in the id_token.
+    pub fn payload_base64_len(&self) -> u32 {
+        base64_len(self.payload_len)
+    }
+
+    /// The claim indices as `uint32` tokens, in the order the contracts unpack them.
+    pub fn into_tokens(self, version: FormatVersion) -> Vec<Token> {
+        let indices = match version {
+            FormatVersion::V1 => vec![
+                self.iss_left_index,
+                self.iss_right_index,
+                self.kid_left_index,
+                self.kid_right_index,
+                self.sub_left_index,
+                self.sub_right_index,
+                self.aud_left_index,
+                self.aud_right_index,
+                self.nonce_left_index,
+                self.iat_left_index,
+                self.exp_left_index,
+            ],
+            FormatVersion::V2 => {
+                let (nbf_left_index, nbf_right_index) = self.nbf.unwrap_or_default();
+                let (azp_left_index, azp_right_index) = self.azp.unwrap_or_default();
+                vec![
+                    self.iss_left_index,
+                    self.iss_right_index,
+                    self.kid_left_index,
+                    self.kid_right_index,
+                    self.sub_left_index,
+                    self.sub_right_index,
+                    self.aud_left_index,
+                    self.aud_right_index,
+                    self.nonce_left_index,
+                    self.nonce_right_index,
+                    self.iat_left_index,
+                    self.iat_right_index,
+                    self.exp_left_index,
+                    self.exp_right_index,
+                    nbf_left_index,
+                    nbf_right_index,
+                    azp_left_index,
+                    azp_right_index,
+                ]
+            }
+        };
+        indices
+            .into_iter()
+            .map(|index| index.to_be_bytes().into_token())
+            .collect()
+    }
+}
+
+fn base64_len(len: u32) -> u32 {
+    (4 * len).div_ceil(3)
+}
+
+fn claim(object: &Object, name: &str, kind: ValueKind) -> Result<(u32, u32)> {
+    optional_claim(object, name, kind)?.ok_or_else(|| Error::MissingClaim(name.to_string()))
+}
+
+fn optional_claim(object: &Object, name: &str, kind: ValueKind) -> Result<Option<(u32, u32)>> {
+    match object.find(name)? {
+        Some(member) if member.kind != kind => {
+            Err(Error::InvalidClaim(format!("{} is not a {:?}", name, kind)))
+        }
+        Some(member) => Ok(Some((member.value.left as u32, member.value.right as u32))),
+        None => Ok(None),
+    }
+}
+
+/// Locate the audience the token is checked against, plus `azp` when present.
+fn audience(object: &Object, expected_aud: Option<&str>) -> Result<(Span, Option<(u32, u32)>)> {
+    let payload = object.json();
+    let aud = object
+        .find("aud")?
+        .ok_or_else(|| Error::MissingClaim("aud".to_string()))?;
+    match aud.kind {
+        ValueKind::String => {
+            if let Some(expected_aud) = expected_aud {
+                if !json::string_eq(payload, aud.value, expected_aud)? {
+                    return Err(Error::InvalidClaim(format!(
+                        "aud does not match {}",
+                        expected_aud
+                    )));
+                }
+            }
+            Ok((aud.value, optional_claim(object, "azp", ValueKind::String)?))
+        }
+        ValueKind::Array => {
+            let mut audiences = vec![];
+            for (span, kind) in json::array_elements(payload, aud.value)? {
+                if kind != ValueKind::String {
+                    return Err(Error::InvalidClaim(format!("aud contains a {:?}", kind)));
+                }
+                audiences.push(span);
+            }
+            let aud = match (expected_aud, audiences.as_slice()) {
+                (Some(expected_aud), _) => {
+                    let mut found = None;
+                    for span in &audiences {
+                        if json::string_eq(payload, *span, expected_aud)? {
+                            found = Some(*span);
+                            break;
+                        }
+                    }
+                    found.ok_or_else(|| {
+                        Error::InvalidClaim(format!("aud does not contain {}", expected_aud))
+                    })?
+                }
+                (None, [span]) => *span,
+                (None, _) => {
+                    return Err(Error::InvalidClaim(format!(
+                        "aud has {} entries, an expected aud is required",
+                        audiences.len()
+                    )))
+                }
+            };
+            // OIDC requires azp whenever the token is issued to more than one audience.
+            let azp = optional_claim(object, "azp", ValueKind::String)?;
+            if azp.is_none() && audiences.len() > 1 {
+                return Err(Error::MissingClaim("azp".to_string()));
+            }
+            Ok((aud, azp))
+        }
+        kind => Err(Error::InvalidClaim(format!(
+            "aud is not a String but a {:?}",
+            kind
+        ))),
+    }
+}
+
+#[test]
+fn test_array_aud() {
+    use crate::id_token::test_token;
+
+    let payload =
+        r#"{"iss":"https://idp","azp":"b","aud":["a", "b"],"sub":"1","nonce":"n","iat":1,"exp":2}"#;
+    let id_token = test_token(payload);
+
+    assert!(ClaimLayout::locate(&id_token, None).is_err());
+    assert!(ClaimLayout::locate(&id_token, Some("c")).is_err());
+
+    let layout = ClaimLayout::locate(&id_token, Some("b")).unwrap();
+    let aud = layout.aud_left_index as usize..layout.aud_right_index as usize;
+    assert_eq!(&payload[aud], "b");
+    let (azp_left_index, azp_right_index) = layout.azp.unwrap();
+    assert_eq!(
+        &payload[azp_left_index as usize..azp_right_index as usize],
+        "b"
+    );
+    assert_eq!(layout.payload_len as usize, payload.len());
+
+    let json = serde_json::to_string(&layout).unwrap();
+    assert_eq!(serde_json::from_str::<ClaimLayout>(&json).unwrap(), layout);
+
+    assert_eq!(layout.into_tokens(FormatVersion::V1).len(), 11);
+    let tokens = layout.into_tokens(FormatVersion::V2);
+    assert_eq!(tokens.len(), FormatVersion::V2.token_names().len());
+    assert_eq!(
+        tokens[9],
+        layout.nonce_right_index.to_be_bytes().into_token()
+    );
+    assert_eq!(tokens[14], 0u32.to_be_bytes().into_token());
+    assert_eq!(tokens[17], azp_right_index.to_be_bytes().into_token());
+}
@@ -0,0 +1,34 @@
+//! Generate the arguments the OpenID-Auth contracts need to validate an OpenID IdToken on chain,
+//! either directly or behind a zero-knowledge proof that hides `sub`.
+
+pub mod artifacts;
+pub mod batch;
+pub mod capacity;
+pub mod ceremony;
+pub mod decode;
+pub mod error;
+pub mod gen_keys;
+pub mod gen_params;
+pub mod header;
+pub mod id_token;
+pub mod import_params;
+pub mod inspect;
+pub mod json;
+pub mod jwks;
+pub mod keys;
+pub mod layout;
+pub mod openid_args;
+pub mod openid_zk_args;
+pub mod pepper;
+pub mod policy;
+pub mod prove;
+pub mod public_input;
+pub mod serve;
+pub mod signature;
+pub mod verify;
+
+pub use artifacts::{ProvingArtifacts, VerifyingArtifacts};
+pub use error::{Error, Result};
+pub use id_token::IdToken;
+pub use layout::ClaimLayout;
+pub use openid_zk_args::{ZkArgs, ZkConfigs};
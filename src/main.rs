@@ -1,24 +1,45 @@
-use clap::{Parser, Subcommand};
-
-use gen_keys::gen_keys;
-use gen_params::gen_params;
-use openid_args::openid_args;
-use openid_zk_args::openid_zk_args;
-use prove::prove;
-use verify::verify_proof;
-
-mod gen_keys;
-mod gen_params;
-mod openid_args;
-mod openid_zk_args;
-mod prove;
-mod verify;
+use std::{
+    io::{Read, Write},
+    net::SocketAddr,
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use prover::utils::{from_0x_hex, to_0x_hex};
+use rand::thread_rng;
+use unipass_openid_auth::{
+    artifacts::{load_params, store_params, store_prover_key, store_verifier_comms},
+    batch::prove_batch,
+    capacity::check_capacity,
+    ceremony::{self, Transcript},
+    decode::{decode_args, render_tree, ArgsKind},
+    gen_keys::{count_proof_commitments, gen_prover_key, gen_verifier_comms, TokenTemplate},
+    gen_params::{gen_params, truncate_params},
+    header::{circuit_id, ArtifactKind, TrustedChecksums},
+    import_params::{import_params, CeremonyFormat},
+    inspect::ArtifactsReport,
+    jwks::{ExportedJwk, Jwks},
+    keys::KeyManifest,
+    layout::FormatVersion,
+    openid_args::openid_args,
+    openid_zk_args::openid_zk_args,
+    pepper::parse_pepper,
+    policy::{Policy, TokenChecks},
+    prove::prove,
+    serve::{serve, ServeConfig},
+    signature::verify_rs256,
+    verify::{parse_proof, parse_public_input, verify_proof, verify_zk_args},
+    ClaimLayout, Error, IdToken, ProvingArtifacts, Result, VerifyingArtifacts, ZkConfigs,
+};
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// print errors as a JSON object on stderr
+    #[arg(long, global = true)]
+    pub json_errors: bool,
 }
 
 #[derive(Debug, Subcommand, Clone)]
@@ -32,21 +53,60 @@ enum Commands {
         #[arg(short, long, default_value = "./build/params.bin")]
         params_path: String,
     },
+    /// Derive the setup parameters of a smaller circuit from larger ones.
+    TruncateParams {
+        /// k parameter of the smaller circuit.
+        #[arg(long)]
+        k: u32,
+        /// setup parameters to truncate
+        #[arg(short, long, default_value = "./build/params.bin")]
+        params_path: String,
+        #[arg(short, long)]
+        output_path: String,
+    },
+    /// Import the setup parameters from a powers-of-tau ceremony transcript.
+    ImportParams {
+        #[arg(long, value_enum)]
+        format: CeremonyFormat,
+        /// ceremony transcript path
+        #[arg(short, long)]
+        input_path: String,
+        /// k parameter for the one email verification circuit.
+        #[arg(long, default_value = "21")]
+        k: u32,
+        /// setup parameters path
+        #[arg(short, long, default_value = "./build/params.bin")]
+        params_path: String,
+    },
+    /// Run a multi-party trusted setup ceremony for the setup parameters.
+    Ceremony {
+        #[command(subcommand)]
+        command: CeremonyCommand,
+    },
     /// Generate proving keys and verifying keys.
     GenKeys {
         /// setup parameters path
         #[arg(short, long, default_value = "./build/params.bin")]
         params_path: String,
 
-        /// emails path
-        #[arg(short, long, default_value = "./build/id_token.txt")]
-        id_token_path: String,
+        /// shape the circuit with this id_token instead of a synthetic one
+        #[arg(short, long)]
+        id_token_path: Option<String>,
+        /// JSON `TokenTemplate` of the synthetic id_token: `claims` and `sub_len`
+        #[arg(long, conflicts_with = "id_token_path")]
+        template_path: Option<String>,
         /// proving key path
         #[arg(long, default_value = "./build/app.pk")]
         pk_path: String,
         /// verifying key file
         #[arg(long, default_value = "./build/app.vc")]
         vc_path: String,
+        /// verifier artifact, all `verify` needs
+        #[arg(long, default_value = "./build/app.vk")]
+        verifier_path: String,
+        /// record the key in this `keys.json` manifest, replacing the one it holds
+        #[arg(long)]
+        manifest_path: Option<String>,
     },
     Prove {
         /// setup parameters path
@@ -74,6 +134,22 @@ enum Commands {
         /// output proof file
         #[arg(long, default_value = "./build/contract_input.json")]
         contract_input_path: String,
+        /// audience to use when `aud` is an array
+        #[arg(long)]
+        expected_aud: Option<String>,
+        /// JWKS to verify the id_token signature with before proving
+        #[arg(long)]
+        jwks_path: Option<String>,
+        /// claim validation policy to enforce before proving
+        #[arg(long)]
+        policy_path: Option<String>,
+        /// `keys.json` manifest naming the key, instead of `--pk-path` and `--vc-path`
+        #[arg(long)]
+        keys_path: Option<String>,
+        /// `sha256sum` output of the params, proving key and verifier comms to load without
+        /// checking their points
+        #[arg(long)]
+        trusted_checksums_path: Option<String>,
     },
     Verify {
         /// setup parameters path
@@ -91,6 +167,10 @@ enum Commands {
         /// output proof file
         #[arg(long, default_value = "./build/public_input.json")]
         public_input_path: String,
+        /// verifier artifact from `gen-keys`, used instead of the params, proving key and
+        /// verifier comms
+        #[arg(long)]
+        verifier_path: Option<String>,
     },
     OpenIdArgs {
         /// emails path
@@ -99,6 +179,22 @@ enum Commands {
         /// emails path
         #[arg(short, long, default_value = "./build/id_token.output")]
         output_path: String,
+        /// audience to use when `aud` is an array
+        #[arg(long)]
+        expected_aud: Option<String>,
+        /// JWKS to verify the id_token signature with before encoding
+        #[arg(long)]
+        jwks_path: Option<String>,
+        /// claim validation policy to enforce before encoding
+        #[arg(long)]
+        policy_path: Option<String>,
+        /// output the claim layout as JSON
+        #[arg(long)]
+        layout_path: Option<String>,
+        /// claim index layout to encode, 2 adds the right indices of nonce, iat and exp and
+        /// the nbf and azp spans
+        #[arg(long, value_enum, default_value_t = FormatVersion::V1)]
+        format_version: FormatVersion,
     },
     OpenIdZKArgs {
         /// setup parameters path
@@ -122,20 +218,324 @@ enum Commands {
         output_path: String,
         #[arg(short, long, default_value = "./build/zkConfigs.json")]
         zk_configs_path: String,
+        /// recompute the public input off-circuit and name the components that differ
+        #[arg(long)]
+        check_public_input: bool,
+        /// audience to use when `aud` is an array
+        #[arg(long)]
+        expected_aud: Option<String>,
+        /// JWKS to verify the id_token signature with before encoding
+        #[arg(long)]
+        jwks_path: Option<String>,
+        /// claim validation policy to enforce before encoding
+        #[arg(long)]
+        policy_path: Option<String>,
+        /// output the claim layout as JSON
+        #[arg(long)]
+        layout_path: Option<String>,
+        /// claim index layout to encode, 2 adds the right indices of nonce, iat and exp and
+        /// the nbf and azp spans
+        #[arg(long, value_enum, default_value_t = FormatVersion::V1)]
+        format_version: FormatVersion,
+        /// `keys.json` manifest naming the key, instead of `--pk-path` and `--vc-path`
+        #[arg(long)]
+        keys_path: Option<String>,
+        /// `sha256sum` output of the params, proving key and verifier comms to load without
+        /// checking their points
+        #[arg(long)]
+        trusted_checksums_path: Option<String>,
+    },
+    /// Check an `open-id-zk-args` output against its zkConfigs and verify its proof.
+    VerifyZkArgs {
+        /// verifier artifact from `gen-keys`, which records how to split `proof_data`
+        #[arg(long, default_value = "./build/app.vk")]
+        verifier_path: String,
+        #[arg(short, long, default_value = "./build/id_token_zk.output")]
+        input_path: String,
+        #[arg(short, long, default_value = "./build/zkConfigs.json")]
+        zk_configs_path: String,
+        /// claim index layout the output was encoded with
+        #[arg(long, value_enum, default_value_t = FormatVersion::V1)]
+        format_version: FormatVersion,
+    },
+    /// Verify the RS256 signature of an id_token against a JWKS.
+    CheckToken {
+        /// id_token file to check
+        #[arg(short, long, default_value = "./build/id_token.txt")]
+        id_token_path: String,
+        /// JWKS or single JWK file
+        #[arg(short, long, default_value = "./build/jwks.json")]
+        jwks_path: String,
+    },
+    /// Export a JWK in the encoding of the contracts' key registry.
+    ExportJwk {
+        /// JWKS or single JWK file, `-` for stdin
+        #[arg(short, long, default_value = "./build/jwks.json")]
+        jwks_path: String,
+        /// kid of the key to export
+        #[arg(long)]
+        kid: String,
+        /// issuer the key belongs to
+        #[arg(long)]
+        iss: String,
+        /// output file, stdout if not set
+        #[arg(short, long)]
+        output_path: Option<String>,
+    },
+    /// Prove the ZK args of every `{id_token, pepper}` line of a JSONL file.
+    ProveBatch {
+        /// setup parameters path
+        #[arg(short, long, default_value = "./build/params.bin")]
+        params_path: String,
+        /// proving key path
+        #[arg(long, default_value = "./build/app.pk")]
+        pk_path: String,
+        #[arg(long, default_value = "./build/app.vc")]
+        vc_path: String,
+        /// JSONL of `{"id_token", "pepper", "expected_aud", "format_version"}` records
+        #[arg(short, long, default_value = "./build/id_tokens.jsonl")]
+        input_path: String,
+        /// JSONL of ZK args, one line per record
+        #[arg(short, long, default_value = "./build/id_tokens_zk.jsonl")]
+        output_path: String,
+        /// proofs to run at the same time, each with its own copy of the proving key
+        /// [default: the available parallelism]
+        #[arg(long)]
+        jobs: Option<usize>,
+        /// JWKS to verify every id_token signature with before proving
+        #[arg(long)]
+        jwks_path: Option<String>,
+        /// claim validation policy to enforce before proving
+        #[arg(long)]
+        policy_path: Option<String>,
+        /// `sha256sum` output of the params, proving key and verifier comms to load without
+        /// checking their points
+        #[arg(long)]
+        trusted_checksums_path: Option<String>,
+    },
+    /// Serve ZK args over HTTP, keeping the setup parameters and proving key in memory.
+    Serve {
+        /// setup parameters path
+        #[arg(short, long, default_value = "./build/params.bin")]
+        params_path: String,
+        /// proving key path
+        #[arg(long, default_value = "./build/app.pk")]
+        pk_path: String,
+        #[arg(long, default_value = "./build/app.vc")]
+        vc_path: String,
+        /// address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: SocketAddr,
+        /// proofs to run at the same time, each clones the proving key while it runs
+        #[arg(long, default_value = "1")]
+        concurrency: usize,
+        /// JWKS to verify every id_token signature with before proving
+        #[arg(long)]
+        jwks_path: Option<String>,
+        /// claim validation policy to enforce before proving
+        #[arg(long)]
+        policy_path: Option<String>,
+        /// `sha256sum` output of the params, proving key and verifier comms to load without
+        /// checking their points
+        #[arg(long)]
+        trusted_checksums_path: Option<String>,
+    },
+    /// Decode an `open-id-args` or `open-id-zk-args` output into its labelled fields.
+    DecodeArgs {
+        /// hex output file to decode
+        #[arg(short, long, default_value = "./build/id_token.output")]
+        input_path: String,
+        /// command the output was produced by
+        #[arg(long, value_enum, default_value_t = ArgsKind::Direct)]
+        kind: ArgsKind,
+        /// claim index layout the output was encoded with
+        #[arg(long, value_enum, default_value_t = FormatVersion::V1)]
+        format_version: FormatVersion,
+        #[arg(long, value_enum, default_value_t = DecodeFormat::Tree)]
+        format: DecodeFormat,
+    },
+    /// Report the SRS, circuit and file metadata of the params, proving key and verifier comms.
+    InspectArtifacts {
+        /// setup parameters path; without any of the three paths, the files in `./build`
+        #[arg(short, long)]
+        params_path: Option<String>,
+        /// proving key path
+        #[arg(long)]
+        pk_path: Option<String>,
+        #[arg(long)]
+        vc_path: Option<String>,
+        /// zkConfigs to check the artifacts against
+        #[arg(short, long)]
+        zk_configs_path: Option<String>,
+        /// print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Subcommand, Clone)]
+enum CeremonyCommand {
+    /// Start a ceremony with params of `tau = 1` and an empty transcript.
+    Init {
+        /// k parameter for the one email verification circuit.
+        #[arg(long, default_value = "21")]
+        k: u32,
+        /// setup parameters path
+        #[arg(short, long, default_value = "./build/params.bin")]
+        params_path: String,
+        #[arg(short, long, default_value = "./build/ceremony.json")]
+        transcript_path: String,
+    },
+    /// Mix a fresh secret into the params and append the contribution to the transcript.
+    Contribute {
+        /// name of the contributing party, recorded in the transcript
+        #[arg(long)]
+        name: String,
+        /// setup parameters path
+        #[arg(short, long, default_value = "./build/params.bin")]
+        params_path: String,
+        #[arg(short, long, default_value = "./build/ceremony.json")]
+        transcript_path: String,
+    },
+    /// Check every contribution of the transcript and that the params are its output.
+    Verify {
+        /// setup parameters path
+        #[arg(short, long, default_value = "./build/params.bin")]
+        params_path: String,
+        #[arg(short, long, default_value = "./build/ceremony.json")]
+        transcript_path: String,
     },
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum DecodeFormat {
+    Json,
+    Tree,
+}
+
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let cli = Cli::parse();
-    match cli.command {
-        Commands::GenParams { k, params_path } => gen_params(k, params_path),
+    match run(cli.command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if cli.json_errors {
+                eprintln!("{}", e.to_json());
+            } else {
+                eprintln!("Error: {}", e);
+            }
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+async fn run(command: Commands) -> Result<()> {
+    match command {
+        Commands::GenParams { k, params_path } => {
+            let pckey = gen_params(k, &mut thread_rng());
+            store_params(&pckey, &params_path)?;
+        }
+        Commands::Ceremony { command } => match command {
+            CeremonyCommand::Init {
+                k,
+                params_path,
+                transcript_path,
+            } => {
+                let (pckey, transcript) = ceremony::init(k);
+                store_params(&pckey, &params_path)?;
+                write_file(&transcript_path, &serde_json::to_vec_pretty(&transcript)?)?;
+            }
+            CeremonyCommand::Contribute {
+                name,
+                params_path,
+                transcript_path,
+            } => {
+                let mut pckey = load_params(&params_path)?;
+                let mut transcript = read_transcript(&transcript_path)?;
+                ceremony::contribute(&mut pckey, &mut transcript, &name, &mut thread_rng())?;
+                store_params(&pckey, &params_path)?;
+                write_file(&transcript_path, &serde_json::to_vec_pretty(&transcript)?)?;
+                println!(
+                    "Contribution hash: {}",
+                    transcript.last_hash().map(to_0x_hex)?
+                );
+            }
+            CeremonyCommand::Verify {
+                params_path,
+                transcript_path,
+            } => {
+                let pckey = load_params(&params_path)?;
+                let transcript = read_transcript(&transcript_path)?;
+                ceremony::verify(&pckey, &transcript, &mut thread_rng())?;
+                for contribution in &transcript.contributions {
+                    println!("{} {}", contribution.hash, contribution.name);
+                }
+                println!(
+                    "Verify success, SRS hash: {}",
+                    to_0x_hex(pckey.sha256_of_srs())
+                );
+            }
+        },
+        Commands::TruncateParams {
+            k,
+            params_path,
+            output_path,
+        } => {
+            let pckey = truncate_params(&load_params(&params_path)?, k)?;
+            store_params(&pckey, &output_path)?;
+            println!("SRS hash: {}", to_0x_hex(pckey.sha256_of_srs()));
+        }
+        Commands::ImportParams {
+            format,
+            input_path,
+            k,
+            params_path,
+        } => {
+            let mut input = std::io::BufReader::new(open_file(&input_path)?);
+            let pckey = import_params(format, &mut input, k, &mut thread_rng())?;
+            store_params(&pckey, &params_path)?;
+            println!("SRS hash: {}", to_0x_hex(pckey.sha256_of_srs()));
+        }
         Commands::GenKeys {
             params_path,
             id_token_path,
+            template_path,
             pk_path,
             vc_path,
-        } => gen_keys(params_path, id_token_path, pk_path, vc_path),
+            verifier_path,
+            manifest_path,
+        } => {
+            let pckey = load_params(&params_path)?;
+            let id_token = match (id_token_path, template_path) {
+                (Some(id_token_path), _) => read_id_token(&id_token_path)?,
+                (None, Some(template_path)) => {
+                    serde_json::from_slice::<TokenTemplate>(&read_file(&template_path)?)
+                        .map_err(|e| {
+                            Error::InvalidArtifact(format!("template {}: {}", template_path, e))
+                        })?
+                        .id_token()?
+                }
+                (None, None) => TokenTemplate::default().id_token()?,
+            };
+            let pk = gen_prover_key(&id_token)?;
+            let (mut prover, verifier_comms) = gen_verifier_comms(&pckey, pk.clone());
+            let mut verifying_artifacts =
+                VerifyingArtifacts::new(&pckey, &prover, verifier_comms, circuit_id(&pk)?);
+            verifying_artifacts.proof_commitments = Some(count_proof_commitments(
+                &pckey,
+                &mut prover,
+                &id_token,
+                &mut thread_rng(),
+            )?);
+            store_prover_key(&pk, &verifying_artifacts, &pk_path)?;
+            store_verifier_comms(&verifying_artifacts, &vc_path)?;
+            verifying_artifacts.store(&verifier_path)?;
+            if let Some(manifest_path) = manifest_path {
+                let manifest =
+                    KeyManifest::new(&pk_path, &vc_path, &verifier_path, &verifying_artifacts)?;
+                write_file(&manifest_path, &serde_json::to_vec_pretty(&manifest)?)?;
+            }
+        }
         Commands::Prove {
             params_path,
             pk_path,
@@ -145,34 +545,75 @@ async fn main() {
             proof_path,
             public_input_path,
             contract_input_path,
-        } => prove(
-            params_path,
-            pk_path,
-            vc_path,
-            pepper,
-            id_token_path,
-            proof_path,
-            public_input_path,
-            contract_input_path,
-        ),
+            expected_aud,
+            jwks_path,
+            policy_path,
+            keys_path,
+            trusted_checksums_path,
+        } => {
+            let checks = read_checks(jwks_path.as_deref(), policy_path.as_deref())?;
+            let token = checks.check(read_id_token(&id_token_path)?, expected_aud.as_deref())?;
+            let pepper = parse_pepper(&pepper)?;
+            check_capacity(token.id_token())?;
+            let mut artifacts = load_proving_artifacts(
+                keys_path.as_deref(),
+                &params_path,
+                &pk_path,
+                &vc_path,
+                &read_trusted_checksums(trusted_checksums_path.as_deref())?,
+            )?;
+            let output = prove(&mut artifacts, &token, &pepper, &mut thread_rng())?;
+
+            write_file(
+                &contract_input_path,
+                &serde_json::to_vec_pretty(&output.contract_input)?,
+            )?;
+            let proof_header = artifacts.header(ArtifactKind::Proof)?;
+            write_file(&proof_path, &proof_header.wrap(&output.proof_bytes()?))?;
+            write_file(
+                &public_input_path,
+                &serde_json::to_vec_pretty(&output.public_input_hex()?)?,
+            )?;
+        }
         Commands::Verify {
             params_path,
             pk_path,
             vc_path,
             proof_path,
             public_input_path,
+            verifier_path,
         } => {
-            let ok = verify_proof(params_path, pk_path, vc_path, proof_path, public_input_path);
-            if ok {
-                println!("Verify success");
-            } else {
-                println!("Verify failed");
+            let public_input = parse_public_input(&read_file(&public_input_path)?)?;
+            let artifacts = load_verifying_artifacts(
+                verifier_path.as_deref(),
+                &params_path,
+                &pk_path,
+                &vc_path,
+            )?;
+            let proof = parse_proof(&artifacts, &read_file(&proof_path)?, &proof_path)?;
+            if !verify_proof(&artifacts, &proof, &public_input) {
+                return Err(Error::VerificationFailed);
             }
+            println!("Verify success");
         }
         Commands::OpenIdArgs {
             id_token_path,
             output_path,
-        } => openid_args(id_token_path, output_path),
+            expected_aud,
+            jwks_path,
+            policy_path,
+            layout_path,
+            format_version,
+        } => {
+            let checks = read_checks(jwks_path.as_deref(), policy_path.as_deref())?;
+            let token = checks.check(read_id_token(&id_token_path)?, expected_aud.as_deref())?;
+            print_azp(token.layout());
+            if let Some(layout_path) = layout_path {
+                write_file(&layout_path, &serde_json::to_vec_pretty(token.layout())?)?;
+            }
+            let data = openid_args(&token, format_version)?;
+            write_file(&output_path, to_0x_hex(data).as_bytes())?;
+        }
         Commands::OpenIdZKArgs {
             params_path,
             pk_path,
@@ -181,14 +622,287 @@ async fn main() {
             id_token_path,
             output_path,
             zk_configs_path,
-        } => openid_zk_args(
+            check_public_input,
+            expected_aud,
+            jwks_path,
+            policy_path,
+            layout_path,
+            format_version,
+            keys_path,
+            trusted_checksums_path,
+        } => {
+            let checks = read_checks(jwks_path.as_deref(), policy_path.as_deref())?;
+            let token = checks.check(read_id_token(&id_token_path)?, expected_aud.as_deref())?;
+            print_azp(token.layout());
+            if let Some(layout_path) = layout_path {
+                write_file(&layout_path, &serde_json::to_vec_pretty(token.layout())?)?;
+            }
+            let pepper = parse_pepper(&pepper)?;
+            check_capacity(token.id_token())?;
+            let mut artifacts = load_proving_artifacts(
+                keys_path.as_deref(),
+                &params_path,
+                &pk_path,
+                &vc_path,
+                &read_trusted_checksums(trusted_checksums_path.as_deref())?,
+            )?;
+            let zk_args = openid_zk_args(
+                &mut artifacts,
+                &token,
+                &pepper,
+                check_public_input,
+                &mut thread_rng(),
+            )?;
+
+            write_file(
+                &zk_configs_path,
+                serde_json::to_string_pretty(&zk_args.zk_configs()?)?.as_bytes(),
+            )?;
+            write_file(
+                &output_path,
+                to_0x_hex(zk_args.encode_packed(format_version)?).as_bytes(),
+            )?;
+        }
+        Commands::VerifyZkArgs {
+            verifier_path,
+            input_path,
+            zk_configs_path,
+            format_version,
+        } => {
+            let input = read_file(&input_path)?;
+            let zk_args = from_0x_hex(String::from_utf8_lossy(&input).trim())
+                .map_err(|e| Error::InvalidArtifact(format!("{}: {:?}", input_path, e)))?;
+            let zk_configs: ZkConfigs = serde_json::from_slice(&read_file(&zk_configs_path)?)
+                .map_err(|e| Error::InvalidArtifact(format!("{}: {}", zk_configs_path, e)))?;
+            let artifacts = VerifyingArtifacts::load(&verifier_path)?;
+            verify_zk_args(&artifacts, &zk_args, format_version, &zk_configs)?;
+            println!("Verify success");
+        }
+        Commands::CheckToken {
+            id_token_path,
+            jwks_path,
+        } => {
+            let id_token = read_id_token(&id_token_path)?;
+            let kid = verify_rs256(&id_token, &read_jwks(&jwks_path)?)?;
+            println!("Signature valid, kid: {}", kid);
+        }
+        Commands::ExportJwk {
+            jwks_path,
+            kid,
+            iss,
+            output_path,
+        } => {
+            let jwks = read_jwks(&jwks_path)?;
+            let key = jwks.find(&kid)?.onchain_key(&iss)?;
+            let exported = serde_json::to_string_pretty(&ExportedJwk::new(&iss, &kid, &key))?;
+            match output_path {
+                Some(output_path) => write_file(&output_path, exported.as_bytes())?,
+                None => println!("{}", exported),
+            }
+        }
+        Commands::ProveBatch {
             params_path,
             pk_path,
             vc_path,
-            pepper,
-            id_token_path,
+            input_path,
             output_path,
+            jobs,
+            jwks_path,
+            policy_path,
+            trusted_checksums_path,
+        } => {
+            let checks = read_checks(jwks_path.as_deref(), policy_path.as_deref())?;
+            let input = std::io::BufReader::new(open_file(&input_path)?);
+            let checksums = read_trusted_checksums(trusted_checksums_path.as_deref())?;
+            let artifacts =
+                ProvingArtifacts::load_trusted(&params_path, &pk_path, &vc_path, &checksums)?;
+            let output = std::io::BufWriter::new(create_file(&output_path)?);
+            let jobs = jobs.unwrap_or_else(|| {
+                std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+            });
+            let summary = prove_batch(artifacts, &checks, jobs, input, output)?;
+            println!(
+                "Proved {} records, {} failed",
+                summary.proved, summary.failed
+            );
+        }
+        Commands::Serve {
+            params_path,
+            pk_path,
+            vc_path,
+            addr,
+            concurrency,
+            jwks_path,
+            policy_path,
+            trusted_checksums_path,
+        } => {
+            let checks = read_checks(jwks_path.as_deref(), policy_path.as_deref())?;
+            let checksums = read_trusted_checksums(trusted_checksums_path.as_deref())?;
+            let artifacts =
+                ProvingArtifacts::load_trusted(&params_path, &pk_path, &vc_path, &checksums)?;
+            let config = ServeConfig {
+                concurrency,
+                checks,
+            };
+            serve(addr, artifacts, config).await?;
+        }
+        Commands::DecodeArgs {
+            input_path,
+            kind,
+            format_version,
+            format,
+        } => {
+            let input = read_file(&input_path)?;
+            let blob = from_0x_hex(String::from_utf8_lossy(&input).trim())
+                .map_err(|e| Error::InvalidArtifact(format!("{}: {:?}", input_path, e)))?;
+            let fields = decode_args(kind, format_version, &blob)?;
+            match format {
+                DecodeFormat::Json => println!("{}", serde_json::to_string_pretty(&fields)?),
+                DecodeFormat::Tree => print!("{}", render_tree(&fields)),
+            }
+        }
+        Commands::InspectArtifacts {
+            params_path,
+            pk_path,
+            vc_path,
             zk_configs_path,
-        ),
+            json,
+        } => {
+            let paths = match (params_path, pk_path, vc_path) {
+                (None, None, None) => (
+                    Some("./build/params.bin".to_string()),
+                    Some("./build/app.pk".to_string()),
+                    Some("./build/app.vc".to_string()),
+                ),
+                paths => paths,
+            };
+            let report = ArtifactsReport::inspect(
+                paths.0.as_deref(),
+                paths.1.as_deref(),
+                paths.2.as_deref(),
+            )?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+            if let Some(zk_configs_path) = zk_configs_path {
+                let zk_configs: ZkConfigs = serde_json::from_slice(&read_file(&zk_configs_path)?)
+                    .map_err(|e| {
+                    Error::InvalidArtifact(format!("{}: {}", zk_configs_path, e))
+                })?;
+                let mismatches = report.check_zk_configs(&zk_configs);
+                if !mismatches.is_empty() {
+                    return Err(Error::ArtifactMismatch(format!(
+                        "{}: {}",
+                        zk_configs_path,
+                        mismatches.join(", ")
+                    )));
+                }
+                println!("{} matches the artifacts", zk_configs_path);
+            }
+            if !report.mismatches.is_empty() {
+                return Err(Error::ArtifactMismatch(report.mismatches.join(", ")));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_azp(layout: &ClaimLayout) {
+    if let Some((azp_left_index, azp_right_index)) = layout.azp {
+        println!("azp_left_index: {azp_left_index}, azp_right_index: {azp_right_index}");
+    }
+}
+
+/// The key of the `keys_path` manifest, or the given pk and vc without one.
+fn load_proving_artifacts(
+    keys_path: Option<&str>,
+    params_path: &str,
+    pk_path: &str,
+    vc_path: &str,
+    checksums: &TrustedChecksums,
+) -> Result<ProvingArtifacts> {
+    let Some(keys_path) = keys_path else {
+        return ProvingArtifacts::load_trusted(params_path, pk_path, vc_path, checksums);
+    };
+    let manifest = KeyManifest::from_json(&read_file(keys_path)?)?;
+    println!(
+        "Using {} of domain size 2^{}",
+        manifest.pk_path,
+        manifest.domain_size.trailing_zeros()
+    );
+    manifest.load(params_path, checksums)
+}
+
+fn load_verifying_artifacts(
+    verifier_path: Option<&str>,
+    params_path: &str,
+    pk_path: &str,
+    vc_path: &str,
+) -> Result<VerifyingArtifacts> {
+    match verifier_path {
+        Some(verifier_path) => VerifyingArtifacts::load(verifier_path),
+        None => Ok(ProvingArtifacts::load(params_path, pk_path, vc_path)?.verifying_artifacts()),
     }
 }
+
+fn read_transcript(path: &str) -> Result<Transcript> {
+    serde_json::from_slice(&read_file(path)?)
+        .map_err(|e| Error::InvalidArtifact(format!("ceremony transcript {}: {}", path, e)))
+}
+
+fn read_trusted_checksums(path: Option<&str>) -> Result<TrustedChecksums> {
+    match path {
+        Some(path) => TrustedChecksums::parse(&String::from_utf8_lossy(&read_file(path)?)),
+        None => Ok(TrustedChecksums::default()),
+    }
+}
+
+fn read_checks(jwks_path: Option<&str>, policy_path: Option<&str>) -> Result<TokenChecks> {
+    let jwks = jwks_path.map(read_jwks).transpose()?;
+    let policy = match policy_path {
+        Some(policy_path) => Some(Policy::from_json(&read_file(policy_path)?)?),
+        None => None,
+    };
+    Ok(TokenChecks { jwks, policy })
+}
+
+fn read_id_token(id_token_path: &str) -> Result<IdToken> {
+    let id_token = read_file(id_token_path)?;
+    IdToken::parse(&String::from_utf8_lossy(&id_token))
+}
+
+fn read_jwks(jwks_path: &str) -> Result<Jwks> {
+    if jwks_path == "-" {
+        let mut jwks = vec![];
+        std::io::stdin().read_to_end(&mut jwks)?;
+        return Jwks::from_json(&jwks);
+    }
+    Jwks::from_json(&read_file(jwks_path)?)
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| Error::io_at(path, e))
+}
+
+fn open_file(path: &str) -> Result<std::fs::File> {
+    std::fs::File::open(path).map_err(|e| Error::io_at(path, e))
+}
+
+fn create_file(path: &str) -> Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .map_err(|e| Error::io_at(path, e))
+}
+
+fn write_file(path: &str, data: &[u8]) -> Result<()> {
+    let mut file = create_file(path)?;
+    file.write_all(data)?;
+    file.flush()?;
+    Ok(())
+}
@@ -1,197 +1,318 @@
-use std::io::Write;
-
-use base64::Engine;
-use ethers::abi::{Token, Tokenizable, Tokenize};
-use plonk::{
-    ark_bn254::{Bn254, Fr},
-    prover::Prover,
-    GeneralEvaluationDomain,
+use ethers::{
+    abi::{Token, Tokenizable, Tokenize},
+    types::U256,
 };
 use prover::{
     circuit::openid::OpenIdCircuit,
-    parameters::{load_params, load_prover_key, load_verifier_comms},
     utils::{convert_proof_array, convert_public_inputs_array, convert_vk_data_array, to_0x_hex},
 };
-use rand::thread_rng;
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
 
+use crate::{
+    capacity::check_capacity,
+    error::{Error, Result},
+    layout::FormatVersion,
+    pepper::parse_pepper,
+    policy::{CheckedToken, TokenChecks},
+    ClaimLayout, IdToken, ProvingArtifacts,
+};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ZkConfigs {
-    srs_hash: String,
-    num_inputs: u64,
-    domain_size: u128,
-    vkdata: String,
+    pub srs_hash: String,
+    pub num_inputs: u64,
+    pub domain_size: u128,
+    pub vkdata: String,
 }
 
-pub fn openid_zk_args(
-    params_path: String,
-    pk_path: String,
-    vc_path: String,
-    pepper: String,
-    id_token_path: String,
-    output_path: String,
-    zk_configs_path: String,
-) {
-    let mut rng = thread_rng();
-    let from_pepper = hex::decode(pepper).unwrap();
-    let id_token = std::fs::read_to_string(&id_token_path).unwrap();
-    let circuit = OpenIdCircuit::new(&id_token, &from_pepper);
-
-    let id_token_hash: [u8; 32] = sha2::Sha256::digest(&circuit.id_token_bytes)
-        .try_into()
-        .unwrap();
-    let sub_peper_hash: [u8; 32] = sha2::Sha256::digest(&circuit.sub_pepper_bytes)
-        .try_into()
-        .unwrap();
+/// Everything the contracts need to verify an id_token without learning its `sub`.
+#[derive(Debug, Clone)]
+pub struct ZkArgs {
+    pub layout: ClaimLayout,
+    pub header_base64_len: u32,
+    pub payload_left_index: u32,
+    pub payload_base64_len: u32,
+    pub id_token_hash: [u8; 32],
+    pub sub_pepper_hash: [u8; 32],
+    pub domain_size: u128,
+    pub header: Vec<u8>,
+    pub payload_pub_match: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub vk_data: Vec<U256>,
+    pub public_inputs: Vec<U256>,
+    pub proof_data: Vec<U256>,
+    pub srs_hash: Vec<u8>,
+    pub num_inputs: u64,
+}
 
-    let mut cs = circuit.synthesize();
-    let public_input = cs.compute_public_input();
+impl ZkArgs {
+    pub fn encode_packed(&self, version: FormatVersion) -> Result<Vec<u8>> {
+        let mut tokens = self.layout.into_tokens(version);
+        tokens.extend([
+            self.header_base64_len.to_be_bytes().into_token(),
+            self.payload_left_index.to_be_bytes().into_token(),
+            self.payload_base64_len.to_be_bytes().into_token(),
+            self.id_token_hash.into_token(),
+            self.sub_pepper_hash.into_token(),
+            self.domain_size.to_be_bytes().into_token(),
+            (self.header.len() as u32).to_be_bytes().into_token(),
+            Token::Bytes(self.header.clone()),
+            (self.payload_pub_match.len() as u32)
+                .to_be_bytes()
+                .into_token(),
+            Token::Bytes(self.payload_pub_match.clone()),
+            (self.signature.len() as u32).to_be_bytes().into_token(),
+            Token::Bytes(self.signature.clone()),
+            (self.vk_data.len() as u32).to_be_bytes().into_token(),
+            self.vk_data.clone().into_token(),
+            (self.public_inputs.len() as u32).to_be_bytes().into_token(),
+            self.public_inputs.clone().into_token(),
+            (self.proof_data.len() as u32).to_be_bytes().into_token(),
+            self.proof_data.clone().into_token(),
+        ]);
+
+        Ok(ethers::abi::encode_packed(&tokens)?)
+    }
+
+    pub fn zk_configs(&self) -> Result<ZkConfigs> {
+        Ok(ZkConfigs {
+            srs_hash: to_0x_hex(&self.srs_hash),
+            num_inputs: self.num_inputs,
+            domain_size: self.domain_size,
+            vkdata: to_0x_hex(encode_vk_data(&self.vk_data)?),
+        })
+    }
+}
 
-    let pckey = load_params(&params_path).unwrap();
-    let sha256_of_srs = pckey.sha256_of_srs();
-    let pk = load_prover_key(&pk_path).unwrap();
-    let vc = load_verifier_comms(&vc_path).unwrap();
-    let mut prover = Prover::<Fr, GeneralEvaluationDomain<Fr>, Bn254>::new(pk);
-    prover.insert_verifier_comms(&vc);
+/// The `vkdata` of `zkConfigs.json`: a `uint32` word count followed by the packed words.
+pub fn encode_vk_data(vk_data: &[U256]) -> Result<Vec<u8>> {
+    let mut vk_data_bytes = (vk_data.len() as u32).to_be_bytes().to_vec();
+    vk_data_bytes.extend_from_slice(&ethers::abi::encode_packed(
+        vk_data.to_vec().into_tokens().as_ref(),
+    )?);
+    Ok(vk_data_bytes)
+}
 
-    let proof = prover.prove(&mut cs, &pckey, &mut rng).unwrap();
+/// Prove that `id_token` hides a `sub` bound to `pepper` and collect the contract arguments.
+///
+/// With `check_public_input`, the public input of the circuit is first checked against the one
+/// recomputed from the token, see [`crate::public_input::check_public_input`].
+pub fn openid_zk_args<R: RngCore>(
+    artifacts: &mut ProvingArtifacts,
+    token: &CheckedToken,
+    pepper: &[u8],
+    check_public_input: bool,
+    rng: &mut R,
+) -> Result<ZkArgs> {
+    let (id_token, layout) = (token.id_token(), token.layout());
+    check_capacity(id_token)?;
+    let circuit = OpenIdCircuit::new(id_token.as_str(), pepper);
+    check_circuit_layout(&circuit, id_token, layout)?;
 
-    let vk_data = convert_vk_data_array(prover.domain, &vc, pckey.vk.beta_h);
-    let proof_data = convert_proof_array(&proof);
-    let public_inputs = convert_public_inputs_array(&public_input);
+    let id_token_hash: [u8; 32] = sha2::Sha256::digest(&circuit.id_token_bytes).into();
+    let sub_pepper_hash: [u8; 32] = sha2::Sha256::digest(&circuit.sub_pepper_bytes).into();
 
-    let id_toeken_split: Vec<_> = id_token.split(".").collect();
-    if id_toeken_split.len() != 3 {
-        panic!("invalid id_token")
+    let mut cs = circuit.synthesize();
+    let public_input = cs.compute_public_input();
+    if check_public_input {
+        crate::public_input::check_public_input(&circuit, &public_input, id_token, layout, pepper)?;
     }
-    let base64url_engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
-    let header = base64url_engine
-        .decode(id_toeken_split[0].as_bytes())
-        .unwrap();
-    let payload = base64url_engine
-        .decode(id_toeken_split[1].as_bytes())
-        .unwrap();
-    let signature = base64url_engine
-        .decode(id_toeken_split[2].as_bytes())
-        .unwrap();
 
-    let field_end_value = r#"","#.as_bytes();
-    let obj_end_value = r#""}"#.as_bytes();
-
-    let iss_left_index = index_of_sub_array(&payload, r#""iss":""#.as_bytes(), 0).unwrap() + 7;
-    let iss_right_index = match index_of_sub_array(&payload, field_end_value, iss_left_index) {
-        Some(iss_right_index) => iss_right_index,
-        None => index_of_sub_array(&payload, obj_end_value, iss_left_index).unwrap(),
-    };
-
-    let kid_left_index = index_of_sub_array(&header, r#""kid":""#.as_bytes(), 0).unwrap() + 7;
-    let kid_right_index = match index_of_sub_array(&header, field_end_value, kid_left_index) {
-        Some(kid_right_index) => kid_right_index,
-        None => index_of_sub_array(&header, obj_end_value, kid_left_index).unwrap(),
-    };
-
-    let iat_left_index = index_of_sub_array(&payload, r#""iat":"#.as_bytes(), 0).unwrap() + 6;
-    let exp_left_index = index_of_sub_array(&payload, r#""exp":"#.as_bytes(), 0).unwrap() + 6;
-
-    let sub_left_index = index_of_sub_array(&payload, r#""sub":""#.as_bytes(), 0).unwrap() + 7;
-    let sub_right_index = match index_of_sub_array(&payload, field_end_value, sub_left_index) {
-        Some(sub_right_index) => sub_right_index,
-        None => index_of_sub_array(&payload, obj_end_value, sub_left_index).unwrap(),
-    };
-
-    let aud_left_index = index_of_sub_array(&payload, r#""aud":""#.as_bytes(), 0).unwrap() + 7;
-    let aud_right_index = match index_of_sub_array(&payload, field_end_value, aud_left_index) {
-        Some(aud_right_index) => aud_right_index,
-        None => index_of_sub_array(&payload, obj_end_value, aud_left_index).unwrap(),
-    };
-
-    let nonce_left_index = index_of_sub_array(&payload, r#""nonce":""#.as_bytes(), 0).unwrap() + 9;
-
-    let data = ethers::abi::encode_packed(&[
-        (iss_left_index as u32).to_be_bytes().into_token(),
-        (iss_right_index as u32).to_be_bytes().into_token(),
-        (kid_left_index as u32).to_be_bytes().into_token(),
-        (kid_right_index as u32).to_be_bytes().into_token(),
-        (sub_left_index as u32).to_be_bytes().into_token(),
-        (sub_right_index as u32).to_be_bytes().into_token(),
-        (aud_left_index as u32).to_be_bytes().into_token(),
-        (aud_right_index as u32).to_be_bytes().into_token(),
-        (nonce_left_index as u32).to_be_bytes().into_token(),
-        (iat_left_index as u32).to_be_bytes().into_token(),
-        (exp_left_index as u32).to_be_bytes().into_token(),
-        (circuit.header_base64_len as u32)
-            .to_be_bytes()
-            .into_token(),
-        (circuit.payload_left_index as u32)
-            .to_be_bytes()
-            .into_token(),
-        (circuit.payload_base64_len as u32)
-            .to_be_bytes()
-            .into_token(),
-        (id_token_hash).into_token(),
-        (sub_peper_hash).into_token(),
-        (prover.domain_size() as u128).to_be_bytes().into_token(),
-        (header.len() as u32).to_be_bytes().into_token(),
-        Token::Bytes(header),
-        (circuit.payload_pub_match.len() as u32)
-            .to_be_bytes()
-            .into_token(),
-        Token::Bytes(circuit.payload_pub_match),
-        (signature.len() as u32).to_be_bytes().into_token(),
-        Token::Bytes(signature),
-        (vk_data.len() as u32).to_be_bytes().into_token(),
-        vk_data.clone().into_token(),
-        (public_inputs.len() as u32).to_be_bytes().into_token(),
-        public_inputs.into_token(),
-        (proof_data.len() as u32).to_be_bytes().into_token(),
-        proof_data.into_token(),
-    ])
-    .unwrap();
+    let proof = artifacts
+        .prover
+        .prove(&mut cs, &artifacts.pckey, rng)
+        .map_err(|e| Error::Prover(format!("{:?}", e)))?;
 
-    let mut vk_data_bytes = (vk_data.len() as u32).to_be_bytes().to_vec();
-    vk_data_bytes.extend_from_slice(
-        &ethers::abi::encode_packed(vk_data.clone().into_tokens().as_ref()).unwrap(),
+    let vk_data = convert_vk_data_array(
+        artifacts.prover.domain,
+        &artifacts.vc,
+        artifacts.pckey.vk.beta_h,
     );
-    let zk_configs = ZkConfigs {
-        srs_hash: to_0x_hex(sha256_of_srs),
+
+    Ok(ZkArgs {
+        layout: *layout,
+        header_base64_len: layout.header_base64_len(),
+        payload_left_index: layout.payload_left_index(),
+        payload_base64_len: layout.payload_base64_len(),
+        id_token_hash,
+        sub_pepper_hash,
+        domain_size: artifacts.prover.domain_size() as u128,
+        header: id_token.header.clone(),
+        payload_pub_match: circuit.payload_pub_match,
+        signature: id_token.signature.clone(),
+        vk_data,
+        public_inputs: convert_public_inputs_array(&public_input),
+        proof_data: convert_proof_array(&proof),
+        srs_hash: artifacts.sha256_of_srs.clone(),
         num_inputs: public_input.len() as u64,
-        domain_size: prover.domain_size() as u128,
-        vkdata: to_0x_hex(vk_data_bytes),
-    };
-
-    let mut file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(&zk_configs_path)
-        .unwrap();
-    file.write(
-        serde_json::to_string_pretty(&zk_configs)
-            .unwrap()
-            .as_bytes(),
-    )
-    .unwrap();
-    file.flush().unwrap();
-
-    let mut file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(&output_path)
-        .unwrap();
-    file.write(to_0x_hex(data).as_bytes()).unwrap();
-    file.flush().unwrap();
+    })
 }
 
-fn index_of_sub_array(array: &[u8], sub_array: &[u8], start: usize) -> Option<usize> {
-    if sub_array.is_empty() {
-        return None;
+/// Check that the proof binds what the contracts read at the offsets of `layout`: the header and
+/// payload where the layout puts them, `sub` at the same span, and every other payload claim in
+/// the clear in `payload_pub_match`.
+pub fn check_circuit_layout(
+    circuit: &OpenIdCircuit,
+    id_token: &IdToken,
+    layout: &ClaimLayout,
+) -> Result<()> {
+    for (name, in_circuit, in_layout) in [
+        ("header_left_index", circuit.header_left_index, 0),
+        (
+            "header_base64_len",
+            circuit.header_base64_len,
+            layout.header_base64_len() as usize,
+        ),
+        (
+            "payload_left_index",
+            circuit.payload_left_index,
+            layout.payload_left_index() as usize,
+        ),
+        (
+            "payload_base64_len",
+            circuit.payload_base64_len,
+            layout.payload_base64_len() as usize,
+        ),
+    ] {
+        if in_circuit != in_layout {
+            return Err(Error::PublicInputMismatch(format!(
+                "{} is {} in the circuit and {} in the claim layout",
+                name, in_circuit, in_layout
+            )));
+        }
     }
-    array[start..]
-        .windows(sub_array.len())
-        .position(|window| window == sub_array)
-        .map(|v| v + start)
+
+    let circuit_sub = circuit.sub_left_index..circuit.sub_left_index + circuit.sub_len;
+    let layout_sub = layout.sub_left_index as usize..layout.sub_right_index as usize;
+    if circuit_sub != layout_sub {
+        return Err(Error::PublicInputMismatch(format!(
+            "sub is at {:?} in the circuit and at {:?} in the claim layout",
+            circuit_sub, layout_sub
+        )));
+    }
+
+    let spans = [
+        ("iss", Some((layout.iss_left_index, layout.iss_right_index))),
+        ("aud", Some((layout.aud_left_index, layout.aud_right_index))),
+        (
+            "nonce",
+            Some((layout.nonce_left_index, layout.nonce_right_index)),
+        ),
+        ("iat", Some((layout.iat_left_index, layout.iat_right_index))),
+        ("exp", Some((layout.exp_left_index, layout.exp_right_index))),
+        ("nbf", layout.nbf),
+        ("azp", layout.azp),
+    ];
+    for (name, span) in spans {
+        let Some((left, right)) = span else {
+            continue;
+        };
+        let span = left as usize..right as usize;
+        let revealed = circuit.payload_pub_match.get(span.clone());
+        if revealed.is_none() || revealed != id_token.payload.get(span.clone()) {
+            return Err(Error::PublicInputMismatch(format!(
+                "{} at {:?} is not revealed in the circuit's payload_pub_match",
+                name, span
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// A token to prove, as posted to `serve` or read from a `prove-batch` JSONL line.
+#[derive(Debug, Deserialize)]
+pub struct ZkArgsRequest {
+    pub id_token: String,
+    /// 32-byte hex pepper, with or without `0x`.
+    pub pepper: String,
+    #[serde(default)]
+    pub expected_aud: Option<String>,
+    #[serde(default)]
+    pub format_version: FormatVersion,
+}
+
+impl ZkArgsRequest {
+    /// Everything short of proving: parse and check the token, locate its claims and decode
+    /// the pepper.
+    pub fn prepare(&self, checks: &TokenChecks) -> Result<(CheckedToken, Vec<u8>)> {
+        let id_token = IdToken::parse(&self.id_token)?;
+        let token = checks.check(id_token, self.expected_aud.as_deref())?;
+        let pepper = parse_pepper(&self.pepper)?;
+        Ok((token, pepper))
+    }
+}
+
+/// What `open-id-zk-args` writes to `id_token_zk.output` and `zkConfigs.json`.
+#[derive(Debug, Serialize)]
+pub struct ZkArgsResponse {
+    pub zk_args: String,
+    pub zk_configs: ZkConfigs,
+    pub layout: ClaimLayout,
+}
+
+impl ZkArgsResponse {
+    pub fn new(zk_args: &ZkArgs, version: FormatVersion) -> Result<Self> {
+        Ok(Self {
+            zk_args: to_0x_hex(zk_args.encode_packed(version)?),
+            zk_configs: zk_args.zk_configs()?,
+            layout: zk_args.layout,
+        })
+    }
+}
+
+/// ZK args of `token` with placeholder hashes, vk data, public input and proof words.
+#[cfg(test)]
+pub(crate) fn test_zk_args(token: &CheckedToken) -> ZkArgs {
+    let layout = *token.layout();
+    ZkArgs {
+        layout,
+        header_base64_len: layout.header_base64_len(),
+        payload_left_index: layout.payload_left_index(),
+        payload_base64_len: layout.payload_base64_len(),
+        id_token_hash: [1; 32],
+        sub_pepper_hash: [2; 32],
+        domain_size: 1 << 10,
+        header: token.id_token().header.clone(),
+        payload_pub_match: vec![0; 8],
+        signature: token.id_token().signature.clone(),
+        vk_data: vec![U256::from(7); 3],
+        public_inputs: vec![U256::from(8)],
+        proof_data: vec![U256::from(9); 2],
+        srs_hash: vec![3; 32],
+        num_inputs: 1,
+    }
+}
+
+#[test]
+fn test_check_circuit_layout() {
+    use crate::id_token::test_token;
+
+    let token = TokenChecks::default()
+        .check(
+            test_token(
+                r#"{"iss":"https://idp","sub":"1","aud":["a","b"],"azp":"a","nonce":"n","iat":1,"exp":2}"#,
+            ),
+            Some("b"),
+        )
+        .unwrap();
+    let (id_token, layout) = (token.id_token(), token.layout());
+    let mut circuit = OpenIdCircuit::new(id_token.as_str(), &[7; 32]);
+    check_circuit_layout(&circuit, id_token, layout).unwrap();
+    let mut parts = id_token.as_str().split('.');
+    assert_eq!(
+        layout.payload_left_index() as usize,
+        parts.next().unwrap().len() + 1
+    );
+    assert_eq!(
+        layout.payload_base64_len() as usize,
+        parts.next().unwrap().len()
+    );
+
+    circuit.payload_pub_match[layout.aud_left_index as usize] = 0;
+    assert!(matches!(
+        check_circuit_layout(&circuit, id_token, layout),
+        Err(Error::PublicInputMismatch(msg)) if msg.starts_with("aud at")
+    ));
 }
@@ -0,0 +1,15 @@
+use crate::error::{Error, Result};
+
+/// Decode a 32-byte pepper given as hex, with or without a `0x` prefix.
+pub fn parse_pepper(pepper: &str) -> Result<Vec<u8>> {
+    let pepper = pepper.trim();
+    let pepper = pepper.strip_prefix("0x").unwrap_or(pepper);
+    let bytes = hex::decode(pepper).map_err(|e| Error::InvalidPepper(e.to_string()))?;
+    if bytes.len() != 32 {
+        return Err(Error::InvalidPepper(format!(
+            "expected 32 bytes, found {}",
+            bytes.len()
+        )));
+    }
+    Ok(bytes)
+}
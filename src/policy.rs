@@ -0,0 +1,207 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use crate::{
+    error::{Error, Result},
+    jwks::Jwks,
+    signature::verify_rs256,
+    ClaimLayout, IdToken,
+};
+
+/// Which tokens we are willing to produce contract arguments for, loaded from a JSON file.
+///
+/// ```json
+/// {
+///   "issuers": [
+///     { "iss": "https://accounts.google.com", "audiences": ["407408718192.apps.googleusercontent.com"] }
+///   ],
+///   "max_token_age": 3600,
+///   "clock_skew": 60,
+///   "require_nbf": false
+/// }
+/// ```
+///
+/// Times are in seconds. An issuer with no audiences accepts any audience.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Policy {
+    pub issuers: Vec<IssuerPolicy>,
+    #[serde(default)]
+    pub max_token_age: Option<u64>,
+    #[serde(default)]
+    pub clock_skew: u64,
+    #[serde(default)]
+    pub require_nbf: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IssuerPolicy {
+    pub iss: String,
+    #[serde(default)]
+    pub audiences: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    iss: String,
+    aud: Audience,
+    iat: u64,
+    exp: u64,
+    nbf: Option<u64>,
+}
+
+impl Policy {
+    pub fn from_json(json: &[u8]) -> Result<Self> {
+        serde_json::from_slice(json).map_err(|e| Error::InvalidArtifact(format!("policy: {}", e)))
+    }
+
+    /// Check `id_token` at unix time `now`, naming the first rule it breaks.
+    ///
+    /// `expected_aud` is the audience the contracts will read, when `aud` is an array.
+    pub fn check(&self, id_token: &IdToken, expected_aud: Option<&str>, now: u64) -> Result<()> {
+        let claims: Claims = serde_json::from_slice(&id_token.payload)
+            .map_err(|e| Error::MalformedJwt(format!("invalid payload: {}", e)))?;
+
+        let issuer = self
+            .issuers
+            .iter()
+            .find(|issuer| issuer.iss == claims.iss)
+            .ok_or_else(|| violation("issuer", format!("{} is not allowed", claims.iss)))?;
+
+        let audiences = match (expected_aud, &claims.aud) {
+            (Some(expected_aud), _) => vec![expected_aud.to_string()],
+            (None, Audience::One(aud)) => vec![aud.clone()],
+            (None, Audience::Many(auds)) => auds.clone(),
+        };
+        if !issuer.audiences.is_empty() {
+            if let Some(aud) = audiences.iter().find(|aud| !issuer.audiences.contains(aud)) {
+                return Err(violation(
+                    "audience",
+                    format!("{} is not allowed for {}", aud, claims.iss),
+                ));
+            }
+        }
+
+        let skew = self.clock_skew;
+        if now > claims.exp.saturating_add(skew) {
+            return Err(violation(
+                "exp",
+                format!("token expired at {}, now is {}", claims.exp, now),
+            ));
+        }
+        if claims.iat > now.saturating_add(skew) {
+            return Err(violation(
+                "iat",
+                format!("token issued at {}, in the future of {}", claims.iat, now),
+            ));
+        }
+        match claims.nbf {
+            Some(nbf) if nbf > now.saturating_add(skew) => {
+                return Err(violation(
+                    "nbf",
+                    format!("token not valid before {}, now is {}", nbf, now),
+                ));
+            }
+            None if self.require_nbf => {
+                return Err(violation("nbf", "token has no nbf".to_string()));
+            }
+            _ => {}
+        }
+        if let Some(max_token_age) = self.max_token_age {
+            let age = now.saturating_sub(claims.iat);
+            if age > max_token_age.saturating_add(skew) {
+                return Err(violation(
+                    "max_token_age",
+                    format!("token is {}s old, at most {}s allowed", age, max_token_age),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The signature and policy checks a token must pass before it is encoded or proved.
+#[derive(Debug, Default)]
+pub struct TokenChecks {
+    pub jwks: Option<Jwks>,
+    pub policy: Option<Policy>,
+}
+
+impl TokenChecks {
+    /// Check `id_token` and locate its claims, for the audience the contracts will read.
+    pub fn check(&self, id_token: IdToken, expected_aud: Option<&str>) -> Result<CheckedToken> {
+        if let Some(jwks) = &self.jwks {
+            verify_rs256(&id_token, jwks)?;
+        }
+        if let Some(policy) = &self.policy {
+            policy.check(&id_token, expected_aud, unix_now()?)?;
+        }
+        let layout = ClaimLayout::locate(&id_token, expected_aud)?;
+        Ok(CheckedToken { id_token, layout })
+    }
+}
+
+/// An id_token that passed [`TokenChecks`], with the claim layout of the checked audience.
+///
+/// Encoding and proving only take checked tokens, so that no caller skips the checks.
+#[derive(Debug, Clone)]
+pub struct CheckedToken {
+    id_token: IdToken,
+    layout: ClaimLayout,
+}
+
+impl CheckedToken {
+    pub fn id_token(&self) -> &IdToken {
+        &self.id_token
+    }
+
+    pub fn layout(&self) -> &ClaimLayout {
+        &self.layout
+    }
+}
+
+/// The current unix time in seconds, to check policies against.
+pub fn unix_now() -> Result<u64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| Error::Other(e.into()))?
+        .as_secs())
+}
+
+fn violation(rule: &'static str, detail: String) -> Error {
+    Error::PolicyViolation { rule, detail }
+}
+
+#[test]
+fn test_policy() {
+    use crate::id_token::test_token;
+
+    let id_token = test_token(
+        r#"{"iss":"https://idp","aud":["a","b"],"azp":"a","nbf":1100,"iat":1000,"exp":2000}"#,
+    );
+    let policy = Policy::from_json(
+        br#"{"issuers":[{"iss":"https://idp","audiences":["a"]}],"max_token_age":600,"clock_skew":30}"#,
+    )
+    .unwrap();
+
+    let rule = |expected_aud, now| match policy.check(&id_token, expected_aud, now) {
+        Ok(()) => "",
+        Err(Error::PolicyViolation { rule, .. }) => rule,
+        Err(e) => panic!("{}", e),
+    };
+    assert_eq!(rule(Some("a"), 1200), "");
+    assert_eq!(rule(Some("b"), 1200), "audience");
+    assert_eq!(rule(None, 1200), "audience");
+    assert_eq!(rule(Some("a"), 2031), "exp");
+    assert_eq!(rule(Some("a"), 950), "iat");
+    assert_eq!(rule(Some("a"), 1050), "nbf");
+    assert_eq!(rule(Some("a"), 1700), "max_token_age");
+    assert_eq!(rule(Some("a"), 1630), "");
+}
@@ -0,0 +1,245 @@
+//! Recompute the public input of the OpenID circuit outside of the circuit.
+//!
+//! The public input is `sha256(id_token_hash | sub_pepper_hash | header_hash |
+//! payload_pub_match_hash | bit locations)`, reduced into the scalar field. Every component is
+//! derived twice, once from the circuit witness and once from the token the way the contracts
+//! see it, so a mismatch can be pinned on a single component.
+
+use plonk::{ark_bn254::Fr, ark_ff::PrimeField};
+use prover::circuit::openid::OpenIdCircuit;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{Error, Result},
+    ClaimLayout, IdToken,
+};
+
+// The lengths `OpenIdCircuit::synthesize` in the `prover` crate pads its inputs to, which size the
+// bit locations. `test_circuit_max_lens` checks them against a synthesized circuit.
+
+/// Padded length of the base64 id_token in the circuit.
+pub const ID_TOKEN_MAX_LEN: usize = 2048;
+/// Padded length of the base64 header in the circuit.
+pub const HEADER_BASE64_MAX_LEN: usize = 512;
+/// Padded length of the base64 payload in the circuit.
+pub const PAYLOAD_BASE64_MAX_LEN: usize = 1536;
+/// Padded length of the decoded payload in the circuit.
+pub const PAYLOAD_RAW_MAX_LEN: usize = 1152;
+/// Padded length of `sub` in the circuit.
+pub const SUB_MAX_LEN: usize = 128;
+
+/// The preimage of the public input, in hashing order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicInputComponents {
+    pub id_token_hash: [u8; 32],
+    pub sub_pepper_hash: [u8; 32],
+    pub header_hash: [u8; 32],
+    pub payload_pub_match_hash: [u8; 32],
+    /// Where the base64 payload sits in the id_token.
+    pub bit_location_id_token_1: Vec<u8>,
+    pub bit_location_payload_base64: Vec<u8>,
+    /// Where the base64 header sits in the id_token.
+    pub bit_location_id_token_2: Vec<u8>,
+    pub bit_location_header_base64: Vec<u8>,
+    /// Where `sub` sits in the decoded payload.
+    pub bit_location_payload_raw: Vec<u8>,
+    pub bit_location_sub: Vec<u8>,
+}
+
+impl PublicInputComponents {
+    /// The components as the circuit witness has them.
+    pub fn from_circuit(circuit: &OpenIdCircuit) -> Self {
+        Self {
+            id_token_hash: Sha256::digest(&circuit.id_token_bytes).into(),
+            sub_pepper_hash: Sha256::digest(&circuit.sub_pepper_bytes).into(),
+            header_hash: Sha256::digest(&circuit.header_raw_bytes).into(),
+            payload_pub_match_hash: Sha256::digest(&circuit.payload_pub_match).into(),
+            bit_location_id_token_1: bit_location(
+                circuit.payload_left_index,
+                circuit.payload_base64_len,
+                ID_TOKEN_MAX_LEN,
+            ),
+            bit_location_payload_base64: bit_location(
+                0,
+                circuit.payload_base64_len,
+                PAYLOAD_BASE64_MAX_LEN,
+            ),
+            bit_location_id_token_2: bit_location(
+                circuit.header_left_index,
+                circuit.header_base64_len,
+                ID_TOKEN_MAX_LEN,
+            ),
+            bit_location_header_base64: bit_location(
+                0,
+                circuit.header_base64_len,
+                HEADER_BASE64_MAX_LEN,
+            ),
+            bit_location_payload_raw: bit_location(
+                circuit.sub_left_index,
+                circuit.sub_len,
+                PAYLOAD_RAW_MAX_LEN,
+            ),
+            bit_location_sub: bit_location(0, circuit.sub_len, SUB_MAX_LEN),
+        }
+    }
+
+    /// The components as the contracts derive them from the token, its claim layout and the
+    /// pepper, with `payload_pub_match` taken from the circuit.
+    pub fn from_token(
+        id_token: &IdToken,
+        layout: &ClaimLayout,
+        pepper: &[u8],
+        payload_pub_match: &[u8],
+    ) -> Self {
+        let mut parts = id_token.as_str().split('.');
+        let header_base64_len = parts.next().map_or(0, str::len);
+        let payload_base64_len = parts.next().map_or(0, str::len);
+        let sub = layout.sub_left_index as usize..layout.sub_right_index as usize;
+        let sub_len = sub.len();
+        let sub_pepper = [id_token.payload.get(sub).unwrap_or_default(), pepper].concat();
+
+        Self {
+            id_token_hash: Sha256::digest(id_token.as_str()).into(),
+            sub_pepper_hash: Sha256::digest(sub_pepper).into(),
+            header_hash: Sha256::digest(&id_token.header).into(),
+            payload_pub_match_hash: Sha256::digest(payload_pub_match).into(),
+            bit_location_id_token_1: bit_location(
+                header_base64_len + 1,
+                payload_base64_len,
+                ID_TOKEN_MAX_LEN,
+            ),
+            bit_location_payload_base64: bit_location(
+                0,
+                payload_base64_len,
+                PAYLOAD_BASE64_MAX_LEN,
+            ),
+            bit_location_id_token_2: bit_location(0, header_base64_len, ID_TOKEN_MAX_LEN),
+            bit_location_header_base64: bit_location(0, header_base64_len, HEADER_BASE64_MAX_LEN),
+            bit_location_payload_raw: bit_location(
+                layout.sub_left_index as usize,
+                sub_len,
+                PAYLOAD_RAW_MAX_LEN,
+            ),
+            bit_location_sub: bit_location(0, sub_len, SUB_MAX_LEN),
+        }
+    }
+
+    /// The names of the components that differ from `other`.
+    pub fn diff(&self, other: &Self) -> Vec<&'static str> {
+        let mut names = vec![];
+        let mut check = |name, equal: bool| {
+            if !equal {
+                names.push(name);
+            }
+        };
+        check("id_token_hash", self.id_token_hash == other.id_token_hash);
+        check(
+            "sub_pepper_hash",
+            self.sub_pepper_hash == other.sub_pepper_hash,
+        );
+        check("header_hash", self.header_hash == other.header_hash);
+        check(
+            "payload_pub_match_hash",
+            self.payload_pub_match_hash == other.payload_pub_match_hash,
+        );
+        check(
+            "bit_location_id_token_1",
+            self.bit_location_id_token_1 == other.bit_location_id_token_1,
+        );
+        check(
+            "bit_location_payload_base64",
+            self.bit_location_payload_base64 == other.bit_location_payload_base64,
+        );
+        check(
+            "bit_location_id_token_2",
+            self.bit_location_id_token_2 == other.bit_location_id_token_2,
+        );
+        check(
+            "bit_location_header_base64",
+            self.bit_location_header_base64 == other.bit_location_header_base64,
+        );
+        check(
+            "bit_location_payload_raw",
+            self.bit_location_payload_raw == other.bit_location_payload_raw,
+        );
+        check(
+            "bit_location_sub",
+            self.bit_location_sub == other.bit_location_sub,
+        );
+        names
+    }
+
+    /// `sha256` of the concatenated components, with the top three bits cleared to fit the
+    /// scalar field.
+    pub fn public_input(&self) -> Fr {
+        let mut hasher = Sha256::new();
+        hasher.update(self.id_token_hash);
+        hasher.update(self.sub_pepper_hash);
+        hasher.update(self.header_hash);
+        hasher.update(self.payload_pub_match_hash);
+        hasher.update(&self.bit_location_id_token_1);
+        hasher.update(&self.bit_location_payload_base64);
+        hasher.update(&self.bit_location_id_token_2);
+        hasher.update(&self.bit_location_header_base64);
+        hasher.update(&self.bit_location_payload_raw);
+        hasher.update(&self.bit_location_sub);
+        let mut hash: [u8; 32] = hasher.finalize().into();
+        hash[0] &= 0x1f;
+        Fr::from_be_bytes_mod_order(&hash)
+    }
+}
+
+/// Check the public input `circuit` synthesized to against the one recomputed from the token.
+///
+/// Fails with [`Error::PublicInputMismatch`] naming every component where the circuit witness
+/// and the token disagree, or `public_input` when the components agree but the circuit hashes
+/// them differently.
+pub fn check_public_input(
+    circuit: &OpenIdCircuit,
+    circuit_public_input: &[Fr],
+    id_token: &IdToken,
+    layout: &ClaimLayout,
+    pepper: &[u8],
+) -> Result<()> {
+    let components = PublicInputComponents::from_circuit(circuit);
+    let expected =
+        PublicInputComponents::from_token(id_token, layout, pepper, &circuit.payload_pub_match);
+    let mut mismatches = components.diff(&expected);
+    if mismatches.is_empty() && circuit_public_input != [components.public_input()] {
+        mismatches.push("public_input");
+    }
+    if !mismatches.is_empty() {
+        return Err(Error::PublicInputMismatch(mismatches.join(", ")));
+    }
+    Ok(())
+}
+
+/// A `max_len` bit mask, most significant bit first, with the bits of `[left, left + len)` set.
+pub fn bit_location(left: usize, len: usize, max_len: usize) -> Vec<u8> {
+    let mut mask = vec![0u8; max_len.div_ceil(8)];
+    for i in left..(left + len).min(max_len) {
+        mask[i / 8] |= 0x80 >> (i % 8);
+    }
+    mask
+}
+
+#[test]
+#[ignore = "synthesizes the full circuit"]
+fn test_circuit_max_lens() {
+    use crate::id_token::test_token;
+
+    let id_token =
+        test_token(r#"{"iss":"https://idp","sub":"1","aud":"app","nonce":"n","iat":1,"exp":2}"#);
+    let layout = ClaimLayout::locate(&id_token, None).unwrap();
+    let pepper = [7u8; 32];
+    let circuit = OpenIdCircuit::new(id_token.as_str(), &pepper);
+    let public_input = circuit.synthesize().compute_public_input();
+    check_public_input(&circuit, &public_input, &id_token, &layout, &pepper).unwrap();
+}
+
+#[test]
+fn test_bit_location() {
+    assert_eq!(bit_location(0, 0, 16), vec![0, 0]);
+    assert_eq!(bit_location(3, 7, 16), vec![0b0001_1111, 0b1100_0000]);
+    assert_eq!(bit_location(14, 8, 16), vec![0, 0b0000_0011]);
+}
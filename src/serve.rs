@@ -0,0 +1,202 @@
+use std::{
+    net::SocketAddr,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+};
+
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, State},
+    http::{Request, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use rand::thread_rng;
+use tokio::sync::Semaphore;
+
+use crate::{
+    error::{Error, Result},
+    openid_zk_args::{openid_zk_args, ZkArgsRequest, ZkArgsResponse},
+    policy::TokenChecks,
+    ProvingArtifacts,
+};
+
+/// How the prover service checks and proves tokens.
+pub struct ServeConfig {
+    /// How many proofs run at the same time, each on its own clone of the proving key.
+    pub concurrency: usize,
+    pub checks: TokenChecks,
+}
+
+struct ServerState {
+    config: ServeConfig,
+    pool: Pool<ProvingArtifacts>,
+}
+
+/// Serve `POST /zk-args` and `GET /health` on `addr` until the process is stopped.
+///
+/// `artifacts` are loaded once and cloned for every proof while it runs.
+pub async fn serve(
+    addr: SocketAddr,
+    artifacts: ProvingArtifacts,
+    config: ServeConfig,
+) -> Result<()> {
+    let concurrency = config.concurrency.max(1);
+    let state = Arc::new(ServerState {
+        config,
+        pool: Pool::new(artifacts, concurrency),
+    });
+
+    let app = Router::new()
+        .route("/health", get(|| async { "ok" }))
+        .route("/zk-args", post(zk_args))
+        .with_state(state);
+
+    println!("Listening on {}", addr);
+    axum::Server::try_bind(&addr)
+        .map_err(|e| Error::Other(e.into()))?
+        .serve(app.into_make_service())
+        .await
+        .map_err(|e| Error::Other(e.into()))
+}
+
+async fn zk_args(
+    State(state): State<Arc<ServerState>>,
+    JsonBody(request): JsonBody<ZkArgsRequest>,
+) -> std::result::Result<Json<ZkArgsResponse>, ServeError> {
+    let (token, pepper) = request.prepare(&state.config.checks)?;
+    let zk_args = state
+        .pool
+        .run(move |artifacts| openid_zk_args(artifacts, &token, &pepper, false, &mut thread_rng()))
+        .await?;
+    Ok(Json(ZkArgsResponse::new(&zk_args, request.format_version)?))
+}
+
+/// Runs blocking tasks, one per permit, each on a fresh clone of a shared template.
+///
+/// The prover's `prove` takes `&mut self` and owns its proving key, so a running task needs its
+/// own copy; idle slots hold nothing and a task that panics or fails leaves nothing behind.
+struct Pool<T> {
+    permits: Semaphore,
+    template: Arc<T>,
+}
+
+impl<T: Clone + Send + Sync + 'static> Pool<T> {
+    fn new(template: T, size: usize) -> Self {
+        Self {
+            permits: Semaphore::new(size),
+            template: Arc::new(template),
+        }
+    }
+
+    /// Run `f` with a clone of the template on a blocking thread.
+    ///
+    /// A panic in `f` becomes an [`Error::Prover`] and drops the clone it interrupted.
+    async fn run<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut T) -> Result<R> + Send + 'static,
+    ) -> Result<R> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| Error::Other(e.into()))?;
+        let template = self.template.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut item = T::clone(&template);
+            panic::catch_unwind(AssertUnwindSafe(|| f(&mut item)))
+                .unwrap_or_else(|panic| Err(Error::from_panic(panic)))
+        })
+        .await
+        // Panics are caught above, the task only fails when the runtime shuts down.
+        .map_err(|e| Error::Prover(format!("proving task failed: {}", e)))?
+    }
+}
+
+/// [`Json`] that rejects a body with the JSON error object rather than plain text.
+struct JsonBody<T>(T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for JsonBody<T>
+where
+    Json<T>: FromRequest<S, B, Rejection = JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = ServeError;
+
+    async fn from_request(
+        req: Request<B>,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(|rejection| ServeError(Error::InvalidRequest(rejection.body_text())))?;
+        Ok(Self(value))
+    }
+}
+
+struct ServeError(Error);
+
+impl From<Error> for ServeError {
+    fn from(e: Error) -> Self {
+        ServeError(e)
+    }
+}
+
+impl IntoResponse for ServeError {
+    fn into_response(self) -> Response {
+        let status = match &self.0 {
+            Error::MalformedJwt(_)
+            | Error::MissingClaim(_)
+            | Error::InvalidClaim(_)
+            | Error::InvalidPepper(_)
+            | Error::InvalidSignature(_)
+            | Error::InvalidKey(_)
+            | Error::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            Error::PolicyViolation { .. } => StatusCode::FORBIDDEN,
+            Error::CircuitCapacityExceeded(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(self.0.to_json())).into_response()
+    }
+}
+
+#[tokio::test]
+async fn test_pool_survives_panics() {
+    let pool = Pool::new(0u32, 1);
+    let panicked = pool
+        .run(|n| -> Result<u32> {
+            *n += 1;
+            panic!("bad token")
+        })
+        .await;
+    assert!(matches!(panicked, Err(Error::Prover(msg)) if msg.contains("bad token")));
+
+    // The interrupted clone is gone, the next task starts from the template.
+    let counted = pool.run(|n| {
+        *n += 1;
+        Ok(*n)
+    });
+    assert_eq!(counted.await.unwrap(), 1);
+    assert_eq!(pool.permits.available_permits(), 1);
+}
+
+#[tokio::test]
+async fn test_json_rejection() {
+    use axum::body::Body;
+
+    let request = Request::post("/zk-args")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"id_token":"x"}"#))
+        .unwrap();
+    let Err(ServeError(e)) = JsonBody::<ZkArgsRequest>::from_request(request, &()).await else {
+        panic!("pepper is missing");
+    };
+    assert!(matches!(&e, Error::InvalidRequest(msg) if msg.contains("pepper")));
+    assert_eq!(
+        ServeError(e).into_response().status(),
+        StatusCode::BAD_REQUEST
+    );
+}
@@ -0,0 +1,102 @@
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{Error, Result},
+    jwks::Jwks,
+    IdToken,
+};
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+/// Verify the RS256 signature of `id_token` with the key of `jwks` matching its header `kid`.
+///
+/// Returns the `kid` of the key that verified the token.
+pub fn verify_rs256(id_token: &IdToken, jwks: &Jwks) -> Result<String> {
+    let header: JwtHeader = serde_json::from_slice(&id_token.header)
+        .map_err(|e| Error::MalformedJwt(format!("invalid header: {}", e)))?;
+    if header.alg != "RS256" {
+        return Err(Error::InvalidSignature(format!(
+            "unsupported alg {}",
+            header.alg
+        )));
+    }
+    let kid = header
+        .kid
+        .ok_or_else(|| Error::MissingClaim("kid".to_string()))?;
+
+    let jwk = jwks.find(&kid)?;
+    if let Some(alg) = &jwk.alg {
+        if alg != "RS256" {
+            return Err(Error::InvalidKey(format!("key {} is for {}", kid, alg)));
+        }
+    }
+    let (n, e) = jwk.rsa_components()?;
+    let public_key = RsaPublicKey::new(BigUint::from_bytes_be(&n), BigUint::from_bytes_be(&e))
+        .map_err(|e| Error::InvalidKey(format!("key {}: {}", kid, e)))?;
+
+    let hashed = Sha256::digest(id_token.signing_input().as_bytes());
+    public_key
+        .verify(Pkcs1v15Sign::new::<Sha256>(), &hashed, &id_token.signature)
+        .map_err(|_| {
+            Error::InvalidSignature(format!("signature does not verify with key {}", kid))
+        })?;
+
+    Ok(kid)
+}
+
+#[test]
+fn test_verify_rs256() {
+    use base64::Engine;
+    use rsa::{traits::PublicKeyParts, RsaPrivateKey};
+
+    let base64url_engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+    let jwks = format!(
+        r#"{{"keys":[{{"kty":"RSA","kid":"k1","alg":"RS256","n":"{}","e":"{}"}}]}}"#,
+        base64url_engine.encode(private_key.n().to_bytes_be()),
+        base64url_engine.encode(private_key.e().to_bytes_be()),
+    );
+    let jwks = Jwks::from_json(jwks.as_bytes()).unwrap();
+
+    let sign = |header: &str, payload: &str| {
+        let signing_input = format!(
+            "{}.{}",
+            base64url_engine.encode(header),
+            base64url_engine.encode(payload)
+        );
+        let signature = private_key
+            .sign(
+                Pkcs1v15Sign::new::<Sha256>(),
+                &Sha256::digest(signing_input.as_bytes()),
+            )
+            .unwrap();
+        format!("{}.{}", signing_input, base64url_engine.encode(signature))
+    };
+
+    let id_token = sign(r#"{"alg":"RS256","kid":"k1"}"#, r#"{"sub":"1"}"#);
+    let kid = verify_rs256(&IdToken::parse(&id_token).unwrap(), &jwks).unwrap();
+    assert_eq!(kid, "k1");
+
+    let forged = format!(
+        "{}.{}.{}",
+        base64url_engine.encode(r#"{"alg":"RS256","kid":"k1"}"#),
+        base64url_engine.encode(r#"{"sub":"2"}"#),
+        id_token.rsplit('.').next().unwrap()
+    );
+    assert!(matches!(
+        verify_rs256(&IdToken::parse(&forged).unwrap(), &jwks),
+        Err(Error::InvalidSignature(_))
+    ));
+
+    let unknown_kid = sign(r#"{"alg":"RS256","kid":"k2"}"#, r#"{"sub":"1"}"#);
+    assert!(matches!(
+        verify_rs256(&IdToken::parse(&unknown_kid).unwrap(), &jwks),
+        Err(Error::InvalidKey(_))
+    ));
+}
@@ -1,53 +1,331 @@
+use ethers::types::U256;
 use plonk::{
-    ark_bn254::{Bn254, Fr},
+    ark_bn254::{Bn254, Fq, Fr, G1Affine},
+    ark_ff::{BigInteger256, PrimeField, Zero},
     ark_serialize::CanonicalDeserialize,
     proof::Proof,
-    prover::Prover,
     verifier::Verifier,
-    GeneralEvaluationDomain,
+    EvaluationDomain,
 };
-use prover::{
-    parameters::{load_params, load_prover_key, load_verifier_comms},
-    utils::from_0x_hex,
+use prover::utils::{
+    convert_proof_array, convert_public_inputs_array, convert_vk_data_array, from_0x_hex, to_0x_hex,
 };
 
+use crate::{
+    decode::{decode_args, uint_field, words_field, ArgsKind},
+    error::{Error, Result},
+    header::{ArtifactHeader, ArtifactKind},
+    layout::FormatVersion,
+    openid_zk_args::encode_vk_data,
+    VerifyingArtifacts, ZkConfigs,
+};
+
+/// Parse the `0x` hex strings of a `public_input.json` file.
+pub fn parse_public_input(json: &[u8]) -> Result<Vec<Fr>> {
+    let str_vec: Vec<String> = serde_json::from_slice(json)
+        .map_err(|e| Error::InvalidArtifact(format!("public input: {}", e)))?;
+    str_vec
+        .into_iter()
+        .map(|str| {
+            let tmp = from_0x_hex(&str)
+                .map_err(|e| Error::InvalidArtifact(format!("public input: {:?}", e)))?;
+            Fr::deserialize(&*tmp)
+                .map_err(|e| Error::InvalidArtifact(format!("public input: {:?}", e)))
+        })
+        .collect()
+}
+
+/// Parse the proof file at `path`, refusing it if its header names other artifacts.
+pub fn parse_proof(
+    artifacts: &VerifyingArtifacts,
+    bytes: &[u8],
+    path: &str,
+) -> Result<Proof<Fr, Bn254>> {
+    let body = match ArtifactHeader::split(bytes, ArtifactKind::Proof, path)? {
+        Some((header, body)) => {
+            artifacts.check_header(&header, path)?;
+            body
+        }
+        None => bytes,
+    };
+    Proof::<Fr, Bn254>::deserialize(body)
+        .map_err(|e| Error::InvalidArtifact(format!("proof {}: {:?}", path, e)))
+}
+
 pub fn verify_proof(
-    params_path: String,
-    pk_path: String,
-    vc_path: String,
-    proof_path: String,
-    public_input_path: String,
+    artifacts: &VerifyingArtifacts,
+    proof: &Proof<Fr, Bn254>,
+    public_input: &[Fr],
 ) -> bool {
-    let pckey = load_params(&params_path).unwrap();
-    let sha256_of_srs = pckey.sha256_of_srs();
-
-    let pk = load_prover_key(&pk_path).unwrap();
-    let vc = load_verifier_comms(&vc_path).unwrap();
-    let mut prover = Prover::<Fr, GeneralEvaluationDomain<Fr>, Bn254>::new(pk);
-    prover.insert_verifier_comms(&vc);
-
-    let public_input: Vec<_> = {
-        let tmp = std::fs::read(&public_input_path).unwrap();
-        let str_vec: Vec<String> = serde_json::from_slice(&tmp).unwrap();
-        str_vec
-            .into_iter()
-            .map(|str| {
-                let tmp = from_0x_hex(&str).unwrap();
-                Fr::deserialize(&*tmp)
-            })
-            .collect::<Result<Vec<_>, _>>()
-            .unwrap()
+    let mut verifier = Verifier::new_with_domain(artifacts.domain, public_input, &artifacts.vc);
+
+    verifier.verify(&artifacts.vk, proof, &artifacts.sha256_of_srs)
+}
+
+/// Rebuild a proof with `commitments` commitments from the `proof_data` words of an output, as
+/// the contracts read it.
+///
+/// `convert_proof_array` writes the commitments as `x, y` pairs, `0, 0` for the point at
+/// infinity, followed by the evaluations, and the proof must convert back to `words`.
+pub fn proof_from_words(words: &[U256], commitments: usize) -> Result<Proof<Fr, Bn254>> {
+    let invalid = |detail: &str| Error::InvalidArtifact(format!("proof_data: {}", detail));
+    if words.len() < 2 * commitments {
+        return Err(invalid(&format!(
+            "{} words, too few for {} commitments",
+            words.len(),
+            commitments
+        )));
+    }
+    let (points, evaluations) = words.split_at(2 * commitments);
+    let commitments = points
+        .chunks(2)
+        .map(|pair| {
+            match (
+                field_from_word::<Fq>(&pair[0]),
+                field_from_word::<Fq>(&pair[1]),
+            ) {
+                (Some(x), Some(y)) if x.is_zero() && y.is_zero() => Some(G1Affine::zero()),
+                (Some(x), Some(y)) => {
+                    Some(G1Affine::new(x, y, false)).filter(G1Affine::is_on_curve)
+                }
+                _ => None,
+            }
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| invalid("a commitment is not a point of G1"))?;
+    let evaluations = evaluations
+        .iter()
+        .map(field_from_word::<Fr>)
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| invalid("an evaluation is not a field element"))?;
+    let proof = Proof {
+        commitments,
+        evaluations,
+    };
+    if convert_proof_array(&proof) != words {
+        return Err(invalid("the words are not those of a proof"));
+    }
+    Ok(proof)
+}
+
+/// The field element `word` encodes, `None` unless it is below the modulus.
+fn field_from_word<F: PrimeField<BigInt = BigInteger256>>(word: &U256) -> Option<F> {
+    F::from_repr(BigInteger256::new(word.0))
+}
+
+/// Check an `open-id-zk-args` output and its `zkConfigs.json` the way the contracts will.
+///
+/// The configs must belong to `artifacts`, the output must carry the configs' domain size and
+/// vk data, and the proof in its `proof_data` must verify against the public inputs in the
+/// output.
+pub fn verify_zk_args(
+    artifacts: &VerifyingArtifacts,
+    zk_args: &[u8],
+    version: FormatVersion,
+    zk_configs: &ZkConfigs,
+) -> Result<()> {
+    let fields = decode_args(ArgsKind::Zk, version, zk_args)?;
+
+    let srs_hash = to_0x_hex(&artifacts.sha256_of_srs);
+    if !zk_configs.srs_hash.eq_ignore_ascii_case(&srs_hash) {
+        return Err(mismatch("srs_hash", &zk_configs.srs_hash, &srs_hash));
+    }
+    let domain_size = artifacts.domain.size() as u128;
+    if zk_configs.domain_size != domain_size {
+        return Err(mismatch("domain_size", zk_configs.domain_size, domain_size));
+    }
+    let vk_data = convert_vk_data_array(artifacts.domain, &artifacts.vc, artifacts.vk.beta_h);
+    let vkdata = to_0x_hex(encode_vk_data(&vk_data)?);
+    if !zk_configs.vkdata.eq_ignore_ascii_case(&vkdata) {
+        return Err(mismatch("vkdata", &zk_configs.vkdata, &vkdata));
+    }
+
+    let embedded_domain_size = uint_field(&fields, "domain_size")?;
+    if embedded_domain_size != domain_size {
+        return Err(mismatch("domain_size", embedded_domain_size, domain_size));
+    }
+    if words_field(&fields, "vk_data")? != vk_data {
+        return Err(Error::ArtifactMismatch(
+            "vk_data of the output does not match vkdata of the configs".to_string(),
+        ));
+    }
+    let public_inputs = words_field(&fields, "public_inputs")?;
+    if public_inputs.len() as u64 != zk_configs.num_inputs {
+        return Err(mismatch(
+            "num_inputs",
+            public_inputs.len(),
+            zk_configs.num_inputs,
+        ));
+    }
+
+    let public_input: Vec<Fr> = public_inputs
+        .iter()
+        .map(|word| {
+            let mut bytes = [0u8; 32];
+            word.to_big_endian(&mut bytes);
+            Fr::from_be_bytes_mod_order(&bytes)
+        })
+        .collect();
+    if convert_public_inputs_array(&public_input) != public_inputs {
+        return Err(Error::InvalidArtifact(
+            "public_inputs are not canonical field elements".to_string(),
+        ));
+    }
+    let commitments = artifacts.proof_commitments.ok_or_else(|| {
+        Error::InvalidArtifact(
+            "the verifier does not record the commitment count of a proof, regenerate it with \
+             gen-keys"
+                .to_string(),
+        )
+    })?;
+    let proof = proof_from_words(&words_field(&fields, "proof_data")?, commitments)?;
+    if !verify_proof(artifacts, &proof, &public_input) {
+        return Err(Error::VerificationFailed);
+    }
+    Ok(())
+}
+
+fn mismatch(name: &str, found: impl std::fmt::Display, expected: impl std::fmt::Display) -> Error {
+    Error::ArtifactMismatch(format!("{} is {}, expected {}", name, found, expected))
+}
+
+#[test]
+fn test_proof_from_words() {
+    use plonk::ark_ec::AffineCurve;
+
+    let proof = Proof::<Fr, Bn254> {
+        commitments: vec![G1Affine::prime_subgroup_generator(), G1Affine::zero()],
+        evaluations: vec![Fr::from(5u64)],
+    };
+    let words = convert_proof_array(&proof);
+    let rebuilt = proof_from_words(&words, 2).unwrap();
+    assert_eq!(rebuilt.commitments, proof.commitments);
+    assert_eq!(rebuilt.evaluations, proof.evaluations);
+
+    assert!(matches!(
+        proof_from_words(&[U256::one(), U256::from(3)], 1),
+        Err(Error::InvalidArtifact(msg)) if msg.contains("not a point of G1")
+    ));
+    assert!(matches!(
+        proof_from_words(&[U256::MAX], 0),
+        Err(Error::InvalidArtifact(msg)) if msg.contains("not a field element")
+    ));
+    assert!(matches!(
+        proof_from_words(&words, 3),
+        Err(Error::InvalidArtifact(msg)) if msg.contains("too few")
+    ));
+}
+
+#[test]
+fn test_verify_zk_args_mismatch() {
+    use plonk::{ark_ec::AffineCurve, kzg10::VKey, GeneralEvaluationDomain};
+
+    use crate::{id_token::test_token, openid_zk_args::test_zk_args, policy::TokenChecks};
+
+    let token = TokenChecks::default()
+        .check(
+            test_token(r#"{"iss":"https://idp","sub":"1","aud":"a","nonce":"n","iat":1,"exp":2}"#),
+            None,
+        )
+        .unwrap();
+    let artifacts = VerifyingArtifacts {
+        domain: GeneralEvaluationDomain::new(1 << 10).unwrap(),
+        vc: vec![],
+        vk: VKey {
+            g: AffineCurve::prime_subgroup_generator(),
+            h: AffineCurve::prime_subgroup_generator(),
+            beta_h: AffineCurve::prime_subgroup_generator(),
+            max_degree: 1 << 10,
+        },
+        sha256_of_srs: vec![3; 32],
+        circuit_id: None,
+        proof_commitments: Some(1),
+    };
+    let mut zk_args = test_zk_args(&token);
+    let blob = zk_args.encode_packed(FormatVersion::V1).unwrap();
+    let vkdata = to_0x_hex(
+        encode_vk_data(&convert_vk_data_array(
+            artifacts.domain,
+            &artifacts.vc,
+            artifacts.vk.beta_h,
+        ))
+        .unwrap(),
+    );
+    let mut zk_configs = ZkConfigs {
+        srs_hash: to_0x_hex([4; 32]),
+        num_inputs: 1,
+        domain_size: 1 << 10,
+        vkdata,
     };
+    let verify = |blob: &[u8], zk_configs: &ZkConfigs| {
+        verify_zk_args(&artifacts, blob, FormatVersion::V1, zk_configs)
+    };
+
+    assert!(matches!(
+        verify(&blob, &zk_configs),
+        Err(Error::ArtifactMismatch(msg)) if msg.starts_with("srs_hash is 0x0404")
+    ));
+    zk_configs.srs_hash = to_0x_hex([3; 32]);
+    zk_configs.domain_size = 1 << 11;
+    assert!(matches!(
+        verify(&blob, &zk_configs),
+        Err(Error::ArtifactMismatch(msg)) if msg == "domain_size is 2048, expected 1024"
+    ));
+    zk_configs.domain_size = 1 << 10;
+    assert!(verify(&blob[..40], &zk_configs).is_err());
 
-    let proof = {
-        let file = std::fs::OpenOptions::new()
-            .read(true)
-            .open(&proof_path)
-            .unwrap();
-        Proof::<Fr, Bn254>::deserialize(file).unwrap()
+    // The output was made with vk data other than the configs'.
+    assert!(matches!(
+        verify(&blob, &zk_configs),
+        Err(Error::ArtifactMismatch(msg)) if msg.starts_with("vk_data of the output")
+    ));
+    zk_args.vk_data = convert_vk_data_array(artifacts.domain, &artifacts.vc, artifacts.vk.beta_h);
+    // `proof_data` holds two words, one commitment and no evaluation, but not a point.
+    let blob = zk_args.encode_packed(FormatVersion::V1).unwrap();
+    assert!(matches!(
+        verify(&blob, &zk_configs),
+        Err(Error::InvalidArtifact(msg)) if msg.contains("not a point of G1")
+    ));
+}
+
+#[test]
+#[ignore = "generates an SRS and keys for the full circuit"]
+fn test_verify_zk_args() {
+    use rand::thread_rng;
+
+    use crate::{
+        gen_keys::{count_proof_commitments, gen_prover_key, gen_verifier_comms, TokenTemplate},
+        gen_params::gen_params,
+        header::circuit_id,
+        openid_zk_args::openid_zk_args,
+        policy::TokenChecks,
+        ProvingArtifacts,
     };
 
-    let mut verifier = Verifier::new(&prover, &public_input, &vc);
+    let mut rng = thread_rng();
+    let pckey = gen_params(21, &mut rng);
+    let id_token = TokenTemplate::default().id_token().unwrap();
+    let pk = gen_prover_key(&id_token).unwrap();
+    let circuit_id = circuit_id(&pk).unwrap();
+    let (mut prover, vc) = gen_verifier_comms(&pckey, pk);
+    let mut verifying_artifacts = VerifyingArtifacts::new(&pckey, &prover, vc.clone(), circuit_id);
+    verifying_artifacts.proof_commitments =
+        Some(count_proof_commitments(&pckey, &mut prover, &id_token, &mut rng).unwrap());
+    let verifying_artifacts =
+        VerifyingArtifacts::from_bytes(&verifying_artifacts.to_bytes().unwrap()).unwrap();
+    let mut artifacts = ProvingArtifacts::new(pckey, prover, vc, circuit_id);
+
+    let token = TokenChecks::default().check(id_token, None).unwrap();
+    let zk_args = openid_zk_args(&mut artifacts, &token, &[7; 32], true, &mut rng).unwrap();
+    let blob = zk_args.encode_packed(FormatVersion::V2).unwrap();
+    let zk_configs = zk_args.zk_configs().unwrap();
+    verify_zk_args(&verifying_artifacts, &blob, FormatVersion::V2, &zk_configs).unwrap();
 
-    verifier.verify(&pckey.vk, &proof, &sha256_of_srs)
+    let mut public_inputs = zk_args.clone();
+    public_inputs.public_inputs[0] += U256::one();
+    let blob = public_inputs.encode_packed(FormatVersion::V2).unwrap();
+    assert!(matches!(
+        verify_zk_args(&verifying_artifacts, &blob, FormatVersion::V2, &zk_configs),
+        Err(Error::VerificationFailed)
+    ));
 }